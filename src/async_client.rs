@@ -0,0 +1,190 @@
+use std::process::{Command, Stdio};
+
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::{check_host, encode_framed, parse_host_with, TevError, TevPacket, DEFAULT_CONNECT_TIMEOUT, DEFAULT_HOST_PATTERNS};
+
+/// An asynchronous connection to a Tev instance, built on top of _tokio_.
+/// This is the async equivalent of [TevClient](crate::TevClient) and supports the same packets.
+/// Constructed using [TevClientAsync::wrap], [TevClientAsync::spawn] or [TevClientAsync::spawn_path_default].
+/// Use [TevClientAsync::send] to send commands.
+///
+/// Just like [TevClient](crate::TevClient) any [AsyncWrite] implementation can be used with [TevClientAsync::from_writer],
+/// for example one half of a `tokio::io::duplex` stream in tests.
+pub struct TevClientAsync<W: AsyncWrite + Unpin = TcpStream> {
+    socket: W,
+    buffer: Vec<u8>,
+}
+
+impl TevClientAsync<TcpStream> {
+    /// Create a [TevClientAsync] from an existing [TcpStream] that's connected to _tev_. If _tev_ may not be running
+    /// yet use [TevClientAsync::spawn] or [TevClientAsync::spawn_path_default] instead.
+    ///
+    /// Just like [TevClient::wrap](crate::TevClient::wrap) this enables `TCP_NODELAY` on the socket.
+    pub fn wrap(socket: TcpStream) -> Self {
+        socket.set_nodelay(true).ok();
+        TevClientAsync::from_writer(socket)
+    }
+
+    /// Create a new [TevClientAsync] by spawning _tev_ assuming it is in `PATH` with the default hostname.
+    pub async fn spawn_path_default() -> Result<TevClientAsync, TevError> {
+        TevClientAsync::spawn(Command::new("tev")).await
+    }
+
+    /// Crate a [TevClientAsync] from a command that spawns _tev_.
+    /// The stdout of _tev_ is read asynchronously until it reports the address to connect to.
+    /// Connecting to that address gives up with [TevError::Timeout] after [DEFAULT_CONNECT_TIMEOUT].
+    ///
+    /// ```no_run
+    /// # use tev_client::{TevClientAsync, TevError, PacketCloseImage};
+    /// # use std::process::Command;
+    /// # async fn run() -> Result<(), TevError> {
+    /// let mut command = Command::new("path/to/tev");
+    /// command.arg("--hostname=127.0.0.1:14159");
+    /// let mut client = TevClientAsync::spawn(command).await?;
+    /// client.send(PacketCloseImage { image_name: "test.exr" }).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn spawn(command: Command) -> Result<TevClientAsync, TevError> {
+        let mut command = tokio::process::Command::from(command);
+        let mut child = command.stdout(Stdio::piped()).spawn()
+            .map_err(|io| TevError::Command { io })?;
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+
+        let mut read = String::new();
+        while let Some(line) = lines.next_line().await.map_err(|io| TevError::Stdout { io })? {
+            if let Some((_, host)) = parse_host_with(&line, DEFAULT_HOST_PATTERNS) {
+                check_host(host)?;
+                let socket = tokio::time::timeout(DEFAULT_CONNECT_TIMEOUT, TcpStream::connect(host)).await
+                    .map_err(|_| TevError::Timeout { host: host.to_string() })?
+                    .map_err(|io| TevError::TcpConnect { host: host.to_string(), io })?;
+                return Ok(TevClientAsync::wrap(socket));
+            }
+
+            read.push_str(&line);
+            read.push('\n');
+        }
+
+        Err(TevError::NoSocketResponse { read })
+    }
+
+    /// Set the `TCP_NODELAY` option on the underlying socket, this is enabled by default.
+    pub fn set_nodelay(&self, nodelay: bool) -> std::io::Result<()> {
+        self.socket.set_nodelay(nodelay)
+    }
+}
+
+impl<W: AsyncWrite + Unpin> TevClientAsync<W> {
+    /// Create a [TevClientAsync] that writes packets to any [AsyncWrite] implementation.
+    ///
+    /// ```
+    /// # use tev_client::{TevClientAsync, PacketCloseImage};
+    /// # use tokio::io::AsyncReadExt;
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> std::io::Result<()> {
+    /// let (writer, mut reader) = tokio::io::duplex(1024);
+    /// let mut client = TevClientAsync::from_writer(writer);
+    /// client.send(PacketCloseImage { image_name: "test.exr" }).await?;
+    /// drop(client);
+    ///
+    /// let mut bytes = Vec::new();
+    /// reader.read_to_end(&mut bytes).await?;
+    /// // each packet starts with its total length, including the length itself
+    /// assert_eq!(bytes[..4], (bytes.len() as u32).to_le_bytes());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_writer(writer: W) -> Self {
+        TevClientAsync { socket: writer, buffer: Vec::new() }
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.socket
+    }
+
+    /// Get a mutable reference to the underlying writer.
+    /// Writing to it directly will probably corrupt the stream of packets.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.socket
+    }
+
+    /// Unwrap this client, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.socket
+    }
+
+    /// Send a command to _tev_ without blocking the executor.
+    /// A command is any struct in this crate that implements [TevPacket].
+    pub async fn send(&mut self, packet: impl TevPacket) -> std::io::Result<()> {
+        encode_framed(&packet, None, &mut self.buffer)?;
+        self.socket.write_all(&self.buffer).await
+    }
+}
+
+impl<W: AsyncWrite + Unpin + std::fmt::Debug> std::fmt::Debug for TevClientAsync<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TevClientAsync")
+            .field("socket", &self.socket)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use tokio::io::AsyncReadExt;
+
+    use crate::{PacketCloseImage, PacketCloseImageOwned, TevClientAsync, TevError};
+    use crate::test_util::{decode_packets, DecodedPacket, MockTev};
+
+    fn close(image_name: &str) -> DecodedPacket {
+        DecodedPacket::CloseImage(PacketCloseImageOwned { image_name: image_name.to_string() })
+    }
+
+    #[tokio::test]
+    async fn send_through_duplex() {
+        let (writer, mut reader) = tokio::io::duplex(1024);
+        let mut client = TevClientAsync::from_writer(writer);
+        client.send(PacketCloseImage { image_name: "a" }).await.unwrap();
+        client.send(PacketCloseImage { image_name: "b" }).await.unwrap();
+
+        let error = client.send(PacketCloseImage { image_name: "a\0b" }).await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        drop(client);
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await.unwrap();
+        assert_eq!(decode_packets(&bytes).unwrap(), [close("a"), close("b")]);
+    }
+
+    #[tokio::test]
+    async fn spawn_mock() {
+        let mock = MockTev::start().unwrap();
+        let mut client = TevClientAsync::spawn(mock.stub_command()).await.unwrap();
+        client.send(PacketCloseImage { image_name: "test" }).await.unwrap();
+        drop(client);
+
+        assert_eq!(mock.finish().unwrap(), [close("test")]);
+    }
+
+    #[tokio::test]
+    async fn spawn_command_error() {
+        let error = TevClientAsync::spawn(Command::new("tev_client_missing_command")).await.unwrap_err();
+        assert!(matches!(error, TevError::Command { .. }), "got {:?}", error);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn spawn_no_socket_response() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo 'tev: unknown option --foo'");
+        match TevClientAsync::spawn(command).await {
+            Err(TevError::NoSocketResponse { read }) => assert_eq!(read, "tev: unknown option --foo\n"),
+            result => panic!("expected NoSocketResponse, got {:?}", result.map(|_| ())),
+        }
+    }
+}
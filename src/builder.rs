@@ -0,0 +1,110 @@
+use std::ffi::OsString;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::{connect_host_timeout, AddressFamily, TevClient, TevError, DEFAULT_CONNECT_TIMEOUT, DEFAULT_HOSTNAME};
+
+/// Collects the configuration of a [TevClient] in one place, before spawning or connecting to _tev_.
+/// Constructed using [TevClient::builder].
+///
+/// ```no_run
+/// # use tev_client::{TevClient, TevError};
+/// # fn main() -> Result<(), TevError> {
+/// let mut client = TevClient::builder()
+///     .program("path/to/tev")
+///     .hostname("127.0.0.1:14159")
+///     .default_grab_focus(true)
+///     .spawn()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TevClientBuilder {
+    program: OsString,
+    hostname: Option<String>,
+    default_grab_focus: bool,
+    nodelay: bool,
+    timeout: Duration,
+}
+
+impl TevClient {
+    /// Start building a [TevClient], see [TevClientBuilder].
+    pub fn builder() -> TevClientBuilder {
+        TevClientBuilder::default()
+    }
+}
+
+impl Default for TevClientBuilder {
+    fn default() -> Self {
+        TevClientBuilder {
+            program: OsString::from("tev"),
+            hostname: None,
+            default_grab_focus: false,
+            nodelay: true,
+            timeout: DEFAULT_CONNECT_TIMEOUT,
+        }
+    }
+}
+
+impl TevClientBuilder {
+    /// The _tev_ executable used by [TevClientBuilder::spawn], by default `tev` which is looked up in `PATH`.
+    pub fn program(mut self, program: impl Into<OsString>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    /// The hostname _tev_ listens on. By default _tev_ picks [DEFAULT_HOSTNAME] itself when spawned,
+    /// and that's also where [TevClientBuilder::connect] connects to.
+    pub fn hostname(mut self, hostname: &str) -> Self {
+        self.hostname = Some(hostname.to_string());
+        self
+    }
+
+    /// See [TevClient::set_default_grab_focus], `false` by default.
+    pub fn default_grab_focus(mut self, grab_focus: bool) -> Self {
+        self.default_grab_focus = grab_focus;
+        self
+    }
+
+    /// See [TevClient::set_nodelay], `true` by default.
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// The timeout for establishing the connection, [DEFAULT_CONNECT_TIMEOUT] by default.
+    /// Note that the time spent waiting for a spawned _tev_ to start is not limited.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The command [TevClientBuilder::spawn] runs, for further customization before passing it to [TevClient::spawn].
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        if let Some(hostname) = &self.hostname {
+            command.arg(format!("--hostname={}", hostname));
+        }
+        command
+    }
+
+    /// Spawn a new _tev_ instance and connect to it, see [TevClient::spawn].
+    pub fn spawn(self) -> Result<TevClient, TevError> {
+        let client = TevClient::spawn_with_timeout(self.command(), self.timeout)?;
+        Ok(self.configure(client))
+    }
+
+    /// Connect to a _tev_ instance that's already running, see [TevClient::connect_timeout].
+    pub fn connect(self) -> Result<TevClient, TevError> {
+        let host = self.hostname.as_deref().unwrap_or(DEFAULT_HOSTNAME);
+        let socket = connect_host_timeout(host, AddressFamily::Any, self.timeout)?;
+        Ok(self.configure(TevClient::wrap(socket)))
+    }
+
+    fn configure(&self, mut client: TevClient) -> TevClient {
+        client.set_default_grab_focus(self.default_grab_focus);
+        // failing to set this only affects latency, just like in TevClient::wrap
+        client.set_nodelay(self.nodelay).ok();
+        client
+    }
+}
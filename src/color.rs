@@ -0,0 +1,37 @@
+//! Conversions between linear values and the sRGB encoding.
+//!
+//! _tev_ expects linear values and applies its own display transform, so these are only needed to compare against
+//! sRGB-encoded references, or to show data that's already sRGB-encoded correctly.
+//! Use [TevClient::update_with](crate::TevClient::update_with) to apply a conversion while an update is serialized.
+
+/// Encode a single linear value using the sRGB transfer function, including the linear segment near zero.
+pub fn encode_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decode a single sRGB-encoded value to a linear value, the inverse of [encode_srgb].
+pub fn decode_srgb(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode all linear `values` in place, see [encode_srgb].
+pub fn linear_to_srgb(values: &mut [f32]) {
+    for value in values {
+        *value = encode_srgb(*value);
+    }
+}
+
+/// Decode all sRGB-encoded `values` in place, see [decode_srgb].
+pub fn srgb_to_linear(values: &mut [f32]) {
+    for value in values {
+        *value = decode_srgb(*value);
+    }
+}
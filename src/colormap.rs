@@ -0,0 +1,132 @@
+use std::io;
+use std::io::Write;
+
+use crate::color::decode_srgb;
+use crate::{ImageData, TevClient, TevImage};
+
+/// A colormap used by [TevClient::show_heatmap] to map scalars in the range `0.0..=1.0` to colors.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Colormap {
+    /// The same value in all channels, from black to white.
+    Grayscale,
+    /// The perceptually uniform colormap from matplotlib, from dark purple to yellow.
+    Viridis,
+}
+
+/// Viridis sampled at 17 evenly spaced points, as sRGB-encoded values.
+const VIRIDIS: [[f32; 3]; 17] = [
+    [0.267, 0.004, 0.329],
+    [0.282, 0.094, 0.416],
+    [0.278, 0.176, 0.482],
+    [0.259, 0.251, 0.525],
+    [0.231, 0.322, 0.545],
+    [0.200, 0.388, 0.553],
+    [0.173, 0.447, 0.557],
+    [0.149, 0.510, 0.557],
+    [0.129, 0.569, 0.549],
+    [0.122, 0.627, 0.533],
+    [0.157, 0.682, 0.502],
+    [0.247, 0.737, 0.451],
+    [0.369, 0.788, 0.384],
+    [0.518, 0.831, 0.294],
+    [0.678, 0.863, 0.188],
+    [0.847, 0.886, 0.098],
+    [0.992, 0.906, 0.145],
+];
+
+impl Colormap {
+    /// The linear RGB color for `t`, which is clamped to `0.0..=1.0` first. NaN is mapped to NaN in all channels.
+    ///
+    /// _tev_ expects linear values, so colormaps defined in sRGB are converted before they're returned.
+    pub fn map(self, t: f32) -> [f32; 3] {
+        if t.is_nan() {
+            return [f32::NAN; 3];
+        }
+        let t = t.clamp(0.0, 1.0);
+
+        match self {
+            Colormap::Grayscale => [t; 3],
+            Colormap::Viridis => {
+                let scaled = t * (VIRIDIS.len() - 1) as f32;
+                let i = (scaled as usize).min(VIRIDIS.len() - 2);
+                let f = scaled - i as f32;
+
+                let mut color = [0.0; 3];
+                for (c, value) in color.iter_mut().enumerate() {
+                    let srgb = VIRIDIS[i][c] * (1.0 - f) + VIRIDIS[i + 1][c] * f;
+                    *value = decode_srgb(srgb);
+                }
+                color
+            }
+        }
+    }
+}
+
+impl<W: Write> TevClient<W> {
+    /// Create a new `R`, `G`, `B` image that shows the row-major scalars in `data` mapped through `colormap`,
+    /// for example to visualize a loss landscape or an attention map.
+    ///
+    /// The values are first normalized from `range`, or from the minimum and maximum finite value in `data`
+    /// if `range` is `None`. Values outside the range are clamped to the ends of the colormap.
+    ///
+    /// ```no_run
+    /// # use tev_client::{Colormap, TevClient};
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// let attention: Vec<f32> = vec![0.1, 0.7, 0.2, 0.0];
+    /// client.show_heatmap("attention", 2, 2, &attention, Colormap::Viridis, None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] without creating the image
+    /// if `data` doesn't contain exactly `width * height` values.
+    pub fn show_heatmap(
+        &mut self,
+        name: &str,
+        width: u32,
+        height: u32,
+        data: &[f32],
+        colormap: Colormap,
+        range: Option<(f32, f32)>,
+    ) -> io::Result<TevImage> {
+        let expected = width as u64 * height as u64;
+        if data.len() as u64 != expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("expected {} values for a {}x{} heatmap, got {}", expected, width, height, data.len()),
+            ));
+        }
+
+        let (min, max) = range.unwrap_or_else(|| {
+            data.iter().filter(|v| v.is_finite())
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &v| (min.min(v), max.max(v)))
+        });
+        // a constant (or empty) heatmap maps everything to the start of the colormap
+        let scale = if max > min { 1.0 / (max - min) } else { 0.0 };
+
+        let rgb: Vec<f32> = data.iter().flat_map(|&v| colormap.map((v - min) * scale)).collect();
+        self.show_image(name, width, height, &["R", "G", "B"], ImageData::F32(&rgb))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Colormap, TevClient};
+    use crate::tests::created_update;
+
+    #[test]
+    fn heatmap_colors() {
+        assert_eq!(Colormap::Grayscale.map(2.0), [1.0; 3]);
+        let start = Colormap::Viridis.map(0.0);
+        let end = Colormap::Viridis.map(1.0);
+        assert!(start[2] > start[0] && start[2] > start[1], "viridis starts purple, got {:?}", start);
+        assert!(end[0] > end[2] && end[1] > end[2], "viridis ends yellow, got {:?}", end);
+
+        let mut client = TevClient::from_writer(Vec::new());
+        client.show_heatmap("test", 2, 1, &[-1.0, 3.0], Colormap::Grayscale, None).unwrap();
+        assert!(client.show_heatmap("test", 2, 2, &[0.0], Colormap::Viridis, None).is_err());
+
+        assert_eq!(created_update(client.get_ref()).data, [0.0, 0.0, 0.0, 1.0, 1.0, 1.0]);
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+
+use crate::{PacketUpdateImage, PacketUpdateImageLayout, TevClient};
+
+/// Sends frames of interleaved pixel data to _tev_, but only the bounding box of the pixels that changed
+/// since the previous frame of the same image. This cuts down the amount of data sent a lot for
+/// mostly static images, for example a render that only converges in a small region.
+///
+/// The last frame of each image is kept in memory, so this uses as much memory as the images themselves.
+///
+/// ```no_run
+/// # use tev_client::{DeltaUpdater, TevClient};
+/// # fn main() -> std::io::Result<()> {
+/// # let mut client: TevClient = unimplemented!();
+/// # let frames: Vec<Vec<f32>> = vec![];
+/// client.create_image("render", 64, 64, &["R", "G", "B"])?;
+/// let mut updater = DeltaUpdater::new().threshold(1e-4);
+/// for frame in &frames {
+///     updater.update(&mut client, "render", 64, 64, &["R", "G", "B"], frame)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DeltaUpdater {
+    threshold: f32,
+    frames: HashMap<String, Frame>,
+    buffer: Vec<f32>,
+}
+
+/// The pixels _tev_ is showing for an image, as far as this updater knows.
+#[derive(Debug, Clone)]
+struct Frame {
+    width: u32,
+    height: u32,
+    channel_names: Vec<String>,
+    data: Vec<f32>,
+}
+
+impl DeltaUpdater {
+    /// Create an updater that doesn't know any images yet, with a threshold of `0.0`.
+    pub fn new() -> Self {
+        DeltaUpdater::default()
+    }
+
+    /// Set the largest difference in any channel for which a pixel still counts as unchanged, `0.0` by default.
+    /// Frames in which no pixel differs by more than this from what was last sent are skipped entirely.
+    ///
+    /// Skipped pixels are compared against the values that were sent, not the ones that were skipped,
+    /// so slow drifts are still sent once they add up to more than the threshold.
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Forget the last frame of image `name`, so the next [DeltaUpdater::update] sends the full frame again.
+    /// This is necessary when the image was closed or recreated in _tev_ without going through this updater.
+    /// Returns whether a frame was known for `name`.
+    pub fn forget(&mut self, name: &str) -> bool {
+        self.frames.remove(name).is_some()
+    }
+
+    /// Update the image `name` to show `data`, which holds `width * height` pixels of interleaved `channel_names`.
+    /// The image must already exist in _tev_ and be at least `width * height` in size.
+    ///
+    /// The full frame is sent for the first update of an image or if its size or channels changed,
+    /// later frames only send the bounding box of the changed pixels.
+    /// Returns the sent region as `(x, y, width, height)`, or `None` if nothing changed and nothing was sent.
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] without sending anything
+    /// if `data` doesn't contain exactly `width * height` values for each channel.
+    /// If sending fails the cached frame is not changed, so the next update sends the same pixels again.
+    pub fn update<W: Write, S: AsRef<str>>(
+        &mut self,
+        client: &mut TevClient<W>,
+        name: &str,
+        width: u32,
+        height: u32,
+        channel_names: &[S],
+        data: &[f32],
+    ) -> io::Result<Option<(u32, u32, u32, u32)>> {
+        let packet = PacketUpdateImage::interleaved(name, channel_names, 0, 0, width, height, data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let previous = self.frames.get(name).filter(|frame| {
+            frame.width == width && frame.height == height
+                && frame.channel_names.iter().map(|c| c.as_str()).eq(channel_names.iter().map(|c| c.as_ref()))
+        });
+
+        let previous = match previous {
+            Some(previous) => previous,
+            None => {
+                client.send_update(PacketUpdateImageLayout { grab_focus: client.default_grab_focus(), ..packet }.as_packet())?;
+                self.frames.insert(name.to_string(), Frame {
+                    width,
+                    height,
+                    channel_names: channel_names.iter().map(|c| c.as_ref().to_string()).collect(),
+                    data: data.to_vec(),
+                });
+                return Ok(Some((0, 0, width, height)));
+            }
+        };
+
+        let channels = channel_names.len();
+        let (x, y, region_width, region_height) = match changed_region(&previous.data, data, width, channels, self.threshold) {
+            Some(region) => region,
+            None => return Ok(None),
+        };
+
+        let row_len = region_width as usize * channels;
+        self.buffer.clear();
+        for row in y..y + region_height {
+            let start = (row as usize * width as usize + x as usize) * channels;
+            self.buffer.extend_from_slice(&data[start..start + row_len]);
+        }
+
+        let packet = PacketUpdateImage::interleaved(name, channel_names, x, y, region_width, region_height, &self.buffer)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        client.send_update(PacketUpdateImageLayout { grab_focus: client.default_grab_focus(), ..packet }.as_packet())?;
+
+        // only now that the region was sent does tev show it
+        let frame = self.frames.get_mut(name).unwrap();
+        for row in y..y + region_height {
+            let start = (row as usize * width as usize + x as usize) * channels;
+            frame.data[start..start + row_len].copy_from_slice(&data[start..start + row_len]);
+        }
+        Ok(Some((x, y, region_width, region_height)))
+    }
+}
+
+/// The bounding box of the pixels in which any channel differs by more than `threshold`,
+/// as `(x, y, width, height)`. A channel that's NaN in only one of the frames always counts as changed.
+fn changed_region(old: &[f32], new: &[f32], width: u32, channels: usize, threshold: f32) -> Option<(u32, u32, u32, u32)> {
+    if channels == 0 || width == 0 {
+        return None;
+    }
+
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (i, (old, new)) in old.chunks_exact(channels).zip(new.chunks_exact(channels)).enumerate() {
+        let changed = old.iter().zip(new).any(|(&a, &b)| (a - b).abs() > threshold || a.is_nan() != b.is_nan());
+        if changed {
+            let x = (i % width as usize) as u32;
+            let y = (i / width as usize) as u32;
+            bounds = Some(match bounds {
+                None => (x, y, x, y),
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            });
+        }
+    }
+
+    bounds.map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DeltaUpdater, TevClient};
+    use crate::tests::single_update;
+
+    #[test]
+    fn delta_updates() {
+        let mut client = TevClient::from_writer(Vec::new());
+        let mut updater = DeltaUpdater::new().threshold(0.1);
+        let mut frame = vec![0.0; 4 * 3 * 2];
+
+        assert_eq!(updater.update(&mut client, "test", 4, 3, &["R", "G"], &frame).unwrap(), Some((0, 0, 4, 3)));
+        assert_eq!(updater.update(&mut client, "test", 4, 3, &["R", "G"], &frame).unwrap(), None);
+
+        frame[(4 + 1) * 2 + 1] = 1.0;
+        frame[(2 * 4 + 2) * 2] = 0.5;
+        frame[0] = 0.05;
+        let start = client.get_ref().len();
+        assert_eq!(updater.update(&mut client, "test", 4, 3, &["R", "G"], &frame).unwrap(), Some((1, 1, 2, 2)));
+        let update = single_update(&client.get_ref()[start..]);
+        assert_eq!((update.x, update.y, update.width, update.height), (1, 1, 2, 2));
+        assert_eq!(update.data, [0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.5, 0.0]);
+
+        // small changes add up against the last sent value
+        frame[0] = 0.15;
+        assert_eq!(updater.update(&mut client, "test", 4, 3, &["R", "G"], &frame).unwrap(), Some((0, 0, 1, 1)));
+
+        assert!(updater.forget("test"));
+        assert_eq!(updater.update(&mut client, "test", 4, 3, &["R", "G"], &frame).unwrap(), Some((0, 0, 4, 3)));
+        assert!(updater.update(&mut client, "test", 4, 3, &["R"], &frame).is_err());
+    }
+}
@@ -0,0 +1,1184 @@
+use std::convert::TryFrom;
+use std::io;
+use std::io::Write;
+use std::net::TcpStream;
+
+use crate::{
+    f16_bits_to_f32, validate_update, ChannelLayout, PacketCloseImage, PacketCreateImage, PacketOpenImage, PacketOpenImageV1,
+    PacketUpdateImage, PacketUpdateImageIter, PacketUpdateImageLayout, PacketVectorGraphics, TevClient, TevProtocol,
+    UpdateImageError,
+};
+
+/// A handle to an image created in _tev_ by [TevClient::create_image].
+/// It remembers the name, size and channels of the image so they don't have to be repeated for every update.
+///
+/// This is only a convenience on top of the packets, which can still be sent directly for more control.
+#[derive(Debug, Clone)]
+pub struct TevImage {
+    name: String,
+    width: u32,
+    height: u32,
+    channel_names: Vec<String>,
+    layout: ChannelLayout,
+}
+
+/// Interleaved, row-major pixel data for [TevClient::show_image] and [TevClient::update].
+#[derive(Debug, Copy, Clone)]
+pub enum ImageData<'a> {
+    /// 8-bit values, these are normalized to the range `0.0..=1.0`.
+    U8(&'a [u8]),
+    /// The raw bits of half precision floats, these are widened to single precision.
+    /// See [PacketUpdateImageF16](crate::PacketUpdateImageF16) for how to get these from `half::f16` values.
+    F16(&'a [u16]),
+    /// Float values, these are sent unchanged.
+    F32(&'a [f32]),
+}
+
+impl ImageData<'_> {
+    /// The number of values, which is the number of pixels times the number of channels.
+    pub fn len(&self) -> usize {
+        match self {
+            ImageData::U8(data) => data.len(),
+            ImageData::F16(data) => data.len(),
+            ImageData::F32(data) => data.len(),
+        }
+    }
+
+    /// Whether there are no values at all.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// How [TevClient::show_diff] computes the difference between two values `a` and `b`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DiffMode {
+    /// `abs(a - b)`, to see where the images differ.
+    Absolute,
+    /// `a - b`, to also see which image is brighter. _tev_ shows negative values, for example with a false-color view.
+    Signed,
+}
+
+/// An image opened or created by the high-level helpers, see [TevClient::image_names].
+#[derive(Debug, Clone)]
+pub(crate) struct TrackedImage {
+    name: String,
+    source: ImageSource,
+}
+
+/// How a [TrackedImage] was made, so it can be made again under a different name.
+#[derive(Debug, Clone)]
+enum ImageSource {
+    Opened { path: String, channel_selector: String },
+    Created { width: u32, height: u32, channel_names: Vec<String> },
+}
+
+/// A guard for an image opened by [TevClient::open_scoped], that closes the image again when dropped.
+/// Use [OpenImageGuard::leak] to keep the image open instead.
+///
+/// The guard borrows the client, use [OpenImageGuard::client] to send other packets while it's alive.
+#[derive(Debug)]
+pub struct OpenImageGuard<'c, W: Write = TcpStream> {
+    client: &'c mut TevClient<W>,
+    image_name: String,
+    leaked: bool,
+}
+
+/// Streams an image to _tev_ one row at a time, for example while it's being rendered.
+/// Constructed using [ScanlineStreamer::new].
+#[derive(Debug)]
+pub struct ScanlineStreamer<'c, W: Write = TcpStream> {
+    client: &'c mut TevClient<W>,
+    image: TevImage,
+    next_row: u32,
+}
+
+impl<W: Write> TevClient<W> {
+    /// Create a new black image in _tev_ and return a [TevImage] handle to it.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// let image = client.create_image("test", 2, 1, &["R", "G", "B"])?;
+    /// image.update(&mut client, 0, 0, 2, 1, &[1.0, 0.0, 0.0, 0.0, 1.0, 0.0])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] without sending anything if the channel names aren't unique.
+    pub fn create_image(&mut self, name: &str, width: u32, height: u32, channel_names: &[&str]) -> io::Result<TevImage> {
+        let packet = PacketCreateImage {
+            image_name: name,
+            grab_focus: self.default_grab_focus,
+            width,
+            height,
+            channel_names,
+        };
+        packet.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.send(packet)?;
+        self.track_image(name, ImageSource::Created {
+            width,
+            height,
+            channel_names: channel_names.iter().map(|c| c.to_string()).collect(),
+        });
+
+        Ok(TevImage {
+            name: name.to_string(),
+            width,
+            height,
+            channel_names: channel_names.iter().map(|c| c.to_string()).collect(),
+            layout: ChannelLayout::interleaved(channel_names.len()),
+        })
+    }
+
+    /// Create a new image and immediately fill it with `data`, returning a [TevImage] handle to it.
+    ///
+    /// This works well with the `image` crate, for example for an `RgbImage` or `Rgba32FImage`:
+    /// ```ignore
+    /// client.show_image("test", image.width(), image.height(), &["R", "G", "B"], ImageData::U8(image.as_raw()))?;
+    /// client.show_image("test", image.width(), image.height(), &["R", "G", "B", "A"], ImageData::F32(image.as_raw()))?;
+    /// ```
+    /// A grayscale image can be shown with a single channel, typically called `"L"`.
+    ///
+    /// The order of `channel_names` determines the mapping from the components of each pixel to channels:
+    /// the `i`-th component is sent as channel `channel_names[i]`. For example BGRA data is shown correctly
+    /// with `&["B", "G", "R", "A"]` and ARGB data with `&["A", "R", "G", "B"]`.
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] without creating the image
+    /// if the length of `data` is not `width * height * channel_names.len()`.
+    pub fn show_image(
+        &mut self,
+        name: &str,
+        width: u32,
+        height: u32,
+        channel_names: &[&str],
+        data: ImageData,
+    ) -> io::Result<TevImage> {
+        let expected = (width as u64 * height as u64).checked_mul(channel_names.len() as u64)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, UpdateImageError::Overflow))?;
+        let actual = data.len();
+        if actual as u64 != expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("expected {} values for a {}x{} image with {} channels, got {}", expected, width, height, channel_names.len(), actual),
+            ));
+        }
+
+        let image = self.create_image(name, width, height, channel_names)?;
+        if expected == 0 {
+            return Ok(image);
+        }
+
+        self.update(name, 0, 0, width, height, channel_names, data)?;
+        Ok(image)
+    }
+
+    /// Create a new image and immediately fill it with the interleaved float values in `data`,
+    /// the same as [TevClient::show_image] with [ImageData::F32].
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// let image = client.create_and_fill("test", 2, 1, &["R", "G", "B"], &[1.0, 0.0, 0.0, 0.0, 0.0, 1.0])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] without creating the image
+    /// if the length of `data` is not `width * height * channel_names.len()`.
+    pub fn create_and_fill(&mut self, name: &str, width: u32, height: u32, channel_names: &[&str], data: &[f32]) -> io::Result<TevImage> {
+        self.show_image(name, width, height, channel_names, ImageData::F32(data))
+    }
+
+    /// Set the `width * height` pixels starting at (`x`, `y`) of the image `name` to `data`,
+    /// with the values of each pixel interleaved in the order of `channel_names`.
+    /// The values are converted to single precision floats as described by [ImageData] while the packet is serialized.
+    ///
+    /// ```no_run
+    /// # use tev_client::{ImageData, TevClient};
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// let half_bits: Vec<u16> = vec![0x3c00; 4 * 4];
+    /// client.update("test", 0, 0, 4, 4, &["L"], ImageData::F16(&half_bits))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if `data` doesn't contain exactly
+    /// `width * height * channel_names.len()` values.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        name: &str,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        channel_names: &[&str],
+        data: ImageData,
+    ) -> io::Result<()> {
+        match data {
+            ImageData::U8(data) => self.update_u8(name, x, y, width, height, channel_names, data),
+            ImageData::F16(data) => {
+                let values = data.iter().map(|&bits| f16_bits_to_f32(bits));
+                self.update_interleaved_values(name, x, y, width, height, channel_names, data.len(), values)
+            }
+            ImageData::F32(data) => {
+                let packet = PacketUpdateImage::interleaved(name, channel_names, x, y, width, height, data)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                self.send_update(PacketUpdateImageLayout { grab_focus: self.default_grab_focus, ..packet }.as_packet())
+            }
+        }
+    }
+
+    /// Create a new image showing the per-value difference between the interleaved images `a` and `b`,
+    /// for example to compare a render against a reference. The difference is computed while the packet is serialized.
+    ///
+    /// ```no_run
+    /// # use tev_client::{DiffMode, TevClient};
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// # let (render, reference): (Vec<f32>, Vec<f32>) = unimplemented!();
+    /// client.show_diff("render - reference", 64, 64, &["R", "G", "B"], &render, &reference, DiffMode::Absolute)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] without creating the image
+    /// if `a` and `b` don't both contain exactly `width * height * channel_names.len()` values.
+    #[allow(clippy::too_many_arguments)]
+    pub fn show_diff(
+        &mut self,
+        name: &str,
+        width: u32,
+        height: u32,
+        channel_names: &[&str],
+        a: &[f32],
+        b: &[f32],
+        mode: DiffMode,
+    ) -> io::Result<TevImage> {
+        let expected = (width as u64 * height as u64).checked_mul(channel_names.len() as u64)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, UpdateImageError::Overflow))?;
+        if a.len() as u64 != expected || b.len() as u64 != expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "expected {} values for a {}x{} image with {} channels, got {} and {}",
+                    expected, width, height, channel_names.len(), a.len(), b.len(),
+                ),
+            ));
+        }
+
+        let image = self.create_image(name, width, height, channel_names)?;
+        if expected == 0 {
+            return Ok(image);
+        }
+
+        let values = a.iter().zip(b).map(move |(&a, &b)| match mode {
+            DiffMode::Absolute => (a - b).abs(),
+            DiffMode::Signed => a - b,
+        });
+        self.update_interleaved_values(name, 0, 0, width, height, channel_names, a.len(), values)?;
+        Ok(image)
+    }
+
+    /// Set only some channels of the `width * height` pixels starting at (`x`, `y`) of the image `name`,
+    /// taking them from interleaved `data` with `total_channels` values per pixel without copying them out first.
+    /// The channels in `channel_names` are the consecutive components of each pixel starting at `channel_index`.
+    ///
+    /// For example to only update the alpha channel from RGBA data:
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// let rgba = vec![0.5; 640 * 480 * 4];
+    /// client.update_channels("render", 0, 0, 640, 480, &["A"], &rgba, 4, 3)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if the channels don't fit within `total_channels`
+    /// or if `data` doesn't contain exactly `width * height * total_channels` values.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_channels(
+        &mut self,
+        name: &str,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        channel_names: &[&str],
+        data: &[f32],
+        total_channels: usize,
+        channel_index: usize,
+    ) -> io::Result<()> {
+        if !matches!(channel_index.checked_add(channel_names.len()), Some(end) if end <= total_channels) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} channels starting at index {} don't fit in {} channels",
+                    channel_names.len(), channel_index, total_channels,
+                ),
+            ));
+        }
+        let expected = (width as u64 * height as u64).checked_mul(total_channels as u64)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, UpdateImageError::Overflow))?;
+        if data.len() as u64 != expected {
+            let e = UpdateImageError::DataSizeMismatch { expected, actual: data.len() };
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, e));
+        }
+
+        let offsets: Vec<u64> = (0..channel_names.len()).map(|c| (channel_index + c) as u64).collect();
+        let strides = vec![total_channels as u64; channel_names.len()];
+
+        // the trailing channels of the last pixel are not used, so they can't be part of the data
+        let used = match offsets.last() {
+            Some(&last) if expected != 0 => (expected - total_channels as u64 + last + 1) as usize,
+            _ => 0,
+        };
+        let packet = PacketUpdateImage::new(
+            name,
+            self.default_grab_focus,
+            channel_names,
+            &offsets,
+            &strides,
+            x, y, width, height,
+            &data[..used],
+        ).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        self.send_update(packet)
+    }
+
+    /// Set the values of a single pixel at (`x`, `y`) of the image `name`, one value per channel in `channel_names`.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// client.update_pixel("test", 4, 2, &["R", "G", "B"], &[1.0, 0.5, 0.0])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if the number of values doesn't match the number of channels.
+    pub fn update_pixel(&mut self, name: &str, x: u32, y: u32, channel_names: &[&str], values: &[f32]) -> io::Result<()> {
+        let layout = ChannelLayout::interleaved(channel_names.len());
+        let packet = PacketUpdateImage::new(
+            name,
+            self.default_grab_focus,
+            channel_names,
+            layout.offsets(),
+            layout.strides(),
+            x, y, 1, 1,
+            values,
+        ).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        self.send_update(packet)
+    }
+
+    /// Set the `R`, `G` and `B` channels of the `width * height` pixels starting at (`x`, `y`) of the image `name`
+    /// from row-major pixel colors. Vector types like `glam::Vec3` can be converted using their `to_array` method.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// let red = [1.0, 0.0, 0.0];
+    /// client.update_rgb_pixels("overlay", 0, 0, 2, 1, &[red, red])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if `pixels` doesn't contain exactly `width * height` colors.
+    pub fn update_rgb_pixels(&mut self, name: &str, x: u32, y: u32, width: u32, height: u32, pixels: &[[f32; 3]]) -> io::Result<()> {
+        let data = pixels.concat();
+        let packet = PacketUpdateImage::interleaved(name, &["R", "G", "B"], x, y, width, height, &data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        self.send_update(PacketUpdateImageLayout { grab_focus: self.default_grab_focus, ..packet }.as_packet())
+    }
+
+    /// Set the `width * height` pixels starting at (`x`, `y`) of the image `name` from 8-bit values,
+    /// for example raw RGB data from a camera. `data` contains the values of all channels interleaved in row-major order,
+    /// each byte is normalized to the range `0.0..=1.0` while the packet is serialized without an intermediate buffer.
+    /// See [TevClient::update_u8_parallel] to normalize large images on multiple threads instead.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// let frame: Vec<u8> = vec![255, 128, 0, 0, 128, 255];
+    /// client.update_u8("camera", 0, 0, 2, 1, &["R", "G", "B"], &frame)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if `data` doesn't contain exactly
+    /// `width * height * channel_names.len()` values.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_u8(
+        &mut self,
+        name: &str,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        channel_names: &[&str],
+        data: &[u8],
+    ) -> io::Result<()> {
+        let values = data.iter().map(|&v| v as f32 / 255.0);
+        self.update_interleaved_values(name, x, y, width, height, channel_names, data.len(), values)
+    }
+
+    /// The same as [TevClient::update_u8], but the values are first normalized into a temporary buffer
+    /// using all available threads, which can be faster for large images like a 4K frame.
+    ///
+    /// That buffer holds a `f32` for every value, so it temporarily takes four times the memory of `data`.
+    /// Measure whether this is actually faster for your images, for small ones starting the threads dominates.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// let frame: Vec<u8> = vec![0; 3840 * 2160 * 3];
+    /// client.update_u8_parallel("camera", 0, 0, 3840, 2160, &["R", "G", "B"], &frame)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] without converting anything if `data` doesn't
+    /// contain exactly `width * height * channel_names.len()` values.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_u8_parallel(
+        &mut self,
+        name: &str,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        channel_names: &[&str],
+        data: &[u8],
+    ) -> io::Result<()> {
+        let layout = ChannelLayout::interleaved(channel_names.len());
+        validate_update(channel_names, layout.offsets(), layout.strides(), width, height, data.len())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let values = normalize_u8_parallel(data);
+        self.update(name, x, y, width, height, channel_names, ImageData::F32(&values))
+    }
+
+    /// The same as [TevClient::update_u8], but for float `data` that's passed through `transform` while
+    /// the packet is serialized, for example one of the conversions in [color](crate::color).
+    ///
+    /// ```no_run
+    /// # use tev_client::{color, TevClient};
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// let linear = vec![0.0, 0.2, 1.0];
+    /// client.update_with("encoded", 0, 0, 1, 1, &["R", "G", "B"], &linear, color::encode_srgb)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if `data` doesn't contain exactly
+    /// `width * height * channel_names.len()` values.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_with(
+        &mut self,
+        name: &str,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        channel_names: &[&str],
+        data: &[f32],
+        transform: fn(f32) -> f32,
+    ) -> io::Result<()> {
+        let values = data.iter().map(move |&v| transform(v));
+        self.update_interleaved_values(name, x, y, width, height, channel_names, data.len(), values)
+    }
+
+    /// The same as [TevClient::update_with] without a transform, but for rows that are padded to `row_pitch` values,
+    /// for example a GPU readback buffer where each row is aligned to 256 bytes.
+    /// The padding is skipped while the packet is serialized, the data is not copied to a temporary buffer first.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// # let readback: Vec<f32> = vec![];
+    /// // 100 RGBA pixels are 1600 bytes, which wgpu pads to 1792 bytes per row
+    /// let row_pitch = 1792 / std::mem::size_of::<f32>();
+    /// client.update_pitched("render", 0, 0, 100, 50, &["R", "G", "B", "A"], &readback, row_pitch)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if `row_pitch` is smaller than
+    /// `width * channel_names.len()`, or if `data` is too short to contain `height` rows.
+    /// The padding after the last row may be left out of `data`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_pitched(
+        &mut self,
+        name: &str,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        channel_names: &[&str],
+        data: &[f32],
+        row_pitch: usize,
+    ) -> io::Result<()> {
+        let row_len = (width as usize).saturating_mul(channel_names.len());
+        if row_pitch < row_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("row pitch {} is smaller than a row of {} values", row_pitch, row_len),
+            ));
+        }
+        let needed = match height {
+            0 => Some(0),
+            _ => (height as usize - 1).checked_mul(row_pitch).and_then(|n| n.checked_add(row_len)),
+        };
+        if !matches!(needed, Some(needed) if data.len() >= needed) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} values are not enough for {} rows with pitch {}", data.len(), height, row_pitch),
+            ));
+        }
+
+        let values = data.chunks(row_pitch.max(1)).take(height as usize)
+            .flat_map(move |row| row[..row_len].iter().copied());
+        self.update_interleaved_values(name, x, y, width, height, channel_names, row_len * height as usize, values)
+    }
+
+    /// The same as [TevClient::update_with] without a transform, but for bottom-up `data` where the first row
+    /// is the bottom of the region, like an OpenGL framebuffer. The rows are reversed while the packet is serialized,
+    /// so the image shows up the right way around without flipping the data first.
+    ///
+    /// `x` and `y` are still the top-left corner of the region in _tev_, which uses top-down coordinates.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// # let framebuffer: Vec<f32> = vec![];
+    /// client.update_flipped("viewport", 0, 0, 640, 480, &["R", "G", "B", "A"], &framebuffer)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if `data` doesn't contain exactly
+    /// `width * height * channel_names.len()` values.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_flipped(
+        &mut self,
+        name: &str,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        channel_names: &[&str],
+        data: &[f32],
+    ) -> io::Result<()> {
+        let row_len = (width as usize).saturating_mul(channel_names.len());
+        let values = data.chunks(row_len.max(1)).rev().flatten().copied();
+        self.update_interleaved_values(name, x, y, width, height, channel_names, data.len(), values)
+    }
+
+    /// Set the `width * height` pixels starting at (`x`, `y`) of the image `name` to the values returned by `f`,
+    /// which is called once for each pixel with its coordinates in the image, row by row.
+    /// The `N` values returned by `f` are the channels in `channel_names`, in order.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// client.create_image("gradient", 256, 256, &["R", "G", "B"])?;
+    /// client.update_from_fn("gradient", 0, 0, 256, 256, &["R", "G", "B"], |x, y| {
+    ///     [x as f32 / 255.0, y as f32 / 255.0, 0.0]
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] without calling `f` if `N` is not the number of channels,
+    /// or if the region doesn't fit in the coordinate range.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_from_fn<const N: usize>(
+        &mut self,
+        name: &str,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        channel_names: &[&str],
+        mut f: impl FnMut(u32, u32) -> [f32; N],
+    ) -> io::Result<()> {
+        if channel_names.len() != N {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("function returns {} values per pixel, but there are {} channels", N, channel_names.len()),
+            ));
+        }
+        let (x_end, y_end) = match (x.checked_add(width), y.checked_add(height)) {
+            (Some(x_end), Some(y_end)) => (x_end, y_end),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, UpdateImageError::Overflow)),
+        };
+        let len = (width as u64 * height as u64).checked_mul(N as u64).and_then(|len| usize::try_from(len).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, UpdateImageError::Overflow))?;
+
+        let mut data = Vec::with_capacity(len);
+        for py in y..y_end {
+            for px in x..x_end {
+                data.extend_from_slice(&f(px, py));
+            }
+        }
+
+        self.update(name, x, y, width, height, channel_names, ImageData::F32(&data))
+    }
+
+    /// Send `data_len` interleaved `values` without collecting them first, unless the legacy protocol needs that.
+    #[allow(clippy::too_many_arguments)]
+    fn update_interleaved_values(
+        &mut self,
+        name: &str,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        channel_names: &[&str],
+        data_len: usize,
+        values: impl Iterator<Item=f32> + Clone,
+    ) -> io::Result<()> {
+        let layout = ChannelLayout::interleaved(channel_names.len());
+        validate_update(channel_names, layout.offsets(), layout.strides(), width, height, data_len)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        match self.protocol {
+            TevProtocol::Current => self.send(PacketUpdateImageIter {
+                image_name: name,
+                grab_focus: self.default_grab_focus,
+                channel_names,
+                x,
+                y,
+                width,
+                height,
+                data: values,
+            }),
+            TevProtocol::Legacy => {
+                // the legacy packets are split per channel, which needs the converted values in memory anyway
+                let values: Vec<f32> = values.collect();
+                self.send_update(PacketUpdateImage {
+                    image_name: name,
+                    grab_focus: self.default_grab_focus,
+                    channel_names,
+                    channel_offsets: layout.offsets(),
+                    channel_strides: layout.strides(),
+                    x,
+                    y,
+                    width,
+                    height,
+                    data: &values,
+                })
+            }
+        }
+    }
+
+    /// Create a new black image with a single channel `L`, for example for depth maps or masks.
+    /// See [TevClient::create_image].
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// client.create_grayscale("depth", 2, 2)?;
+    /// client.update_grayscale("depth", 0, 0, 2, 2, &[0.1, 0.2, 0.3, 0.4])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_grayscale(&mut self, name: &str, width: u32, height: u32) -> io::Result<TevImage> {
+        self.create_image(name, width, height, &["L"])
+    }
+
+    /// Create a new black image with channels grouped into layers, for example one layer per render pass.
+    /// Each `(layer, channels)` pair is expanded to the channel names `layer.channel`, which _tev_ groups by layer.
+    /// An empty layer name adds the channels without a prefix. See [TevClient::create_image].
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// let image = client.create_layered("render", 640, 480, &[
+    ///     ("", &["R", "G", "B"]),
+    ///     ("diffuse", &["R", "G", "B"]),
+    ///     ("depth", &["Z"]),
+    /// ])?;
+    /// assert_eq!(image.channel_names()[3], "diffuse.R");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_layered(&mut self, name: &str, width: u32, height: u32, layers: &[(&str, &[&str])]) -> io::Result<TevImage> {
+        let channel_names: Vec<String> = layers.iter()
+            .flat_map(|&(layer, channels)| channels.iter().map(move |&channel| match layer {
+                "" => channel.to_string(),
+                _ => format!("{}.{}", layer, channel),
+            }))
+            .collect();
+        let channel_names: Vec<&str> = channel_names.iter().map(|c| c.as_str()).collect();
+        self.create_image(name, width, height, &channel_names)
+    }
+
+    /// Set the `L` channel of the `width * height` pixels starting at (`x`, `y`) of the image `name`,
+    /// `data` contains one value per pixel in row-major order.
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if `data` doesn't contain exactly `width * height` values.
+    pub fn update_grayscale(&mut self, name: &str, x: u32, y: u32, width: u32, height: u32, data: &[f32]) -> io::Result<()> {
+        let packet = PacketUpdateImage::interleaved(name, &["L"], x, y, width, height, data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        self.send_update(PacketUpdateImageLayout { grab_focus: self.default_grab_focus, ..packet }.as_packet())
+    }
+
+    /// Open all `(path, channel_selector, grab_focus)` entries in `images` with a single write,
+    /// so _tev_ receives them together. Each packet carries its own `grab_focus` flag.
+    ///
+    /// The packets are sent in order, so if multiple entries have `grab_focus` set
+    /// _tev_ ends up focusing the last one of them.
+    ///
+    /// Nothing is sent if any of the packets can't be encoded.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// client.open_images(&[("diffuse.exr", "", false), ("beauty.exr", "", true), ("normal.exr", "", false)])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open_images(&mut self, images: &[(&str, &str, bool)]) -> io::Result<()> {
+        self.buffer.clear();
+        for &(path, channel_selector, grab_focus) in images {
+            let packet = PacketOpenImage { image_name: path, grab_focus, channel_selector };
+            self.protocol.append_open(&packet, self.max_packet_size, &mut self.buffer)?;
+        }
+        self.write_buffer()?;
+
+        for &(path, channel_selector, _) in images {
+            self.track_opened(path, channel_selector);
+        }
+        Ok(())
+    }
+
+    /// Close all images in `image_names`, for example to clear _tev_ of throwaway images.
+    /// A failure to close one image doesn't stop the others from being closed,
+    /// instead the index and error of each image that failed is returned.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// for (index, error) in client.close_images(&["a.exr", "b.exr"]) {
+    ///     eprintln!("failed to close image {}: {}", index, error);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn close_images(&mut self, image_names: &[&str]) -> Vec<(usize, io::Error)> {
+        image_names.iter().enumerate()
+            .filter_map(|(i, &image_name)| self.close_tracked(image_name).err().map(|e| (i, e)))
+            .collect()
+    }
+
+    /// Make _tev_ focus the already open image `name`, without changing it.
+    ///
+    /// _tev_ has no dedicated focus command, so this sends a [PacketVectorGraphics] with `grab_focus: true`,
+    /// `append: true` and no commands. That keeps both the pixels and any existing vector graphics as they are,
+    /// unlike a [PacketReloadImage](crate::PacketReloadImage) which would reload the image from disk.
+    /// Vector graphics packets are only understood by _tev_ 1.17 and newer.
+    ///
+    /// Older versions don't know that packet, so with [TevProtocol::Legacy] a [PacketOpenImageV1] with `grab_focus: true`
+    /// is sent instead. _tev_ then opens `name` as a path, so this only works for images that were opened from a file.
+    pub fn focus_image(&mut self, name: &str) -> io::Result<()> {
+        match self.protocol {
+            TevProtocol::Current => self.send(PacketVectorGraphics {
+                image_name: name,
+                grab_focus: true,
+                append: true,
+                commands: &[],
+            }),
+            TevProtocol::Legacy => self.send(PacketOpenImageV1 { image_name: name, grab_focus: true }),
+        }
+    }
+
+    /// The number of images currently open in _tev_ because of this client, see [TevClient::image_names].
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
+
+    /// The names of the images currently open in _tev_ because of this client, in the order they were opened.
+    /// These are the names _tev_ uses, so images opened with a channel selector are listed as `path:channel_selector`.
+    ///
+    /// Only the high-level helpers like [TevClient::create_image], [TevClient::open_images], [TevClient::close_images]
+    /// and [TevImage::close] are tracked, packets sent directly with [TevClient::send] are not.
+    /// Images closed by the user in the _tev_ window itself are not noticed either.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// client.create_image("test", 2, 2, &["L"])?;
+    /// client.open_images(&[("beauty.exr", "", false)])?;
+    /// assert_eq!(client.image_names().collect::<Vec<_>>(), ["test", "beauty.exr"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn image_names(&self) -> impl Iterator<Item=&str> {
+        self.images.iter().map(|image| image.name.as_str())
+    }
+
+    /// Rename the tracked image `old_name` to `new_name`, by making a new image the same way and closing the old one.
+    /// _tev_ has no command to rename images, so this is only a simulation:
+    ///
+    /// * An image created with [TevClient::create_image] is created again with the same size and channels,
+    ///   but the pixels are not cached by this client so the new image is black until it's updated again.
+    /// * An image opened from a path is opened again from `new_name` with the same channel selector,
+    ///   so `new_name` should be a path to the same (or a renamed) file. If the channel selector is not empty
+    ///   the new image is named `new_name:channel_selector` by _tev_, see [PacketOpenImage::opened_image_name].
+    ///
+    /// Returns an error with kind [io::ErrorKind::NotFound] without sending anything
+    /// if `old_name` is not in [TevClient::image_names].
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// client.create_image("render", 2, 2, &["R", "G", "B"])?;
+    /// client.rename_image("render", "render (old)")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rename_image(&mut self, old_name: &str, new_name: &str) -> io::Result<()> {
+        let source = match self.images.iter().find(|image| image.name == old_name) {
+            Some(image) => image.source.clone(),
+            None => return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("image '{}' was not opened or created by this client", old_name),
+            )),
+        };
+
+        match &source {
+            ImageSource::Opened { channel_selector, .. } => {
+                self.send_open(PacketOpenImage {
+                    image_name: new_name,
+                    grab_focus: self.default_grab_focus,
+                    channel_selector,
+                })?;
+                self.track_opened(new_name, channel_selector);
+            }
+            ImageSource::Created { width, height, channel_names } => {
+                let channel_names: Vec<&str> = channel_names.iter().map(|c| c.as_str()).collect();
+                self.create_image(new_name, *width, *height, &channel_names)?;
+            }
+        }
+
+        self.close_tracked(old_name)
+    }
+
+    fn track_image(&mut self, name: &str, source: ImageSource) {
+        // creating an image with an existing name replaces it in tev, so each name is only tracked once
+        match self.images.iter_mut().find(|image| image.name == name) {
+            Some(image) => image.source = source,
+            None => self.images.push(TrackedImage { name: name.to_string(), source }),
+        }
+    }
+
+    /// Close all tracked images that were opened from `path`, whatever channel selector they were opened with.
+    ///
+    /// [PacketCloseImage] needs the name _tev_ gave the image, which is not the path if a channel selector was used,
+    /// see [PacketOpenImage::opened_image_name]. This resolves those names using the images tracked by this client.
+    ///
+    /// Returns an error with kind [io::ErrorKind::NotFound] without sending anything
+    /// if no image in [TevClient::image_names] was opened from `path`.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// client.open_images(&[("render.exr", "albedo", false), ("render.exr", "normal", false)])?;
+    /// // closes both "render.exr:albedo" and "render.exr:normal"
+    /// client.close_by_path("render.exr")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn close_by_path(&mut self, path: &str) -> io::Result<()> {
+        let names: Vec<String> = self.images.iter()
+            .filter(|image| matches!(&image.source, ImageSource::Opened { path: p, .. } if p == path))
+            .map(|image| image.name.clone())
+            .collect();
+        if names.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no image was opened from path '{}' by this client", path),
+            ));
+        }
+
+        for name in names {
+            self.close_tracked(&name)?;
+        }
+        Ok(())
+    }
+
+    fn track_opened(&mut self, path: &str, channel_selector: &str) -> String {
+        let name = PacketOpenImage { image_name: path, grab_focus: false, channel_selector }.opened_image_name();
+        self.track_image(&name, ImageSource::Opened {
+            path: path.to_string(),
+            channel_selector: channel_selector.to_string(),
+        });
+        name
+    }
+
+    /// Close every image in [TevClient::image_names], for example to clear _tev_ of everything this client made.
+    /// A failure to close one image doesn't stop the others from being closed, instead the name and error of each
+    /// image that failed is returned. Those images stay tracked, so closing them can be tried again later.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// for (name, error) in client.close_all_tracked() {
+    ///     eprintln!("failed to close image {}: {}", name, error);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn close_all_tracked(&mut self) -> Vec<(String, io::Error)> {
+        let mut errors = vec![];
+        for image in std::mem::take(&mut self.images) {
+            if let Err(e) = self.send(PacketCloseImage { image_name: &image.name }) {
+                errors.push((image.name.clone(), e));
+                self.images.push(image);
+            }
+        }
+        errors
+    }
+
+    fn close_tracked(&mut self, image_name: &str) -> io::Result<()> {
+        self.send(PacketCloseImage { image_name })?;
+        self.images.retain(|image| image.name != image_name);
+        Ok(())
+    }
+
+    /// Open the image at `path` like [PacketOpenImage] and close it again when the returned guard is dropped.
+    ///
+    /// ```no_run
+    /// # use tev_client::TevClient;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// {
+    ///     let _guard = client.open_scoped("test.exr", "")?;
+    ///     // do some work, the image is closed even if this returns early or panics
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open_scoped(&mut self, path: &str, channel_selector: &str) -> io::Result<OpenImageGuard<'_, W>> {
+        self.send_open(PacketOpenImage {
+            image_name: path,
+            grab_focus: self.default_grab_focus,
+            channel_selector,
+        })?;
+        let image_name = self.track_opened(path, channel_selector);
+
+        Ok(OpenImageGuard {
+            client: self,
+            image_name,
+            leaked: false,
+        })
+    }
+}
+
+impl<W: Write> OpenImageGuard<'_, W> {
+    /// The name _tev_ gave the guarded image, see [PacketOpenImage::opened_image_name].
+    pub fn image_name(&self) -> &str {
+        &self.image_name
+    }
+
+    /// The client the image was opened with.
+    pub fn client(&mut self) -> &mut TevClient<W> {
+        self.client
+    }
+
+    /// Drop this guard without closing the image.
+    pub fn leak(mut self) {
+        self.leaked = true;
+    }
+}
+
+impl<W: Write> Drop for OpenImageGuard<'_, W> {
+    fn drop(&mut self) {
+        if !self.leaked {
+            // errors can't be reported from drop, and panicking here could abort during unwinding
+            self.client.close_tracked(&self.image_name).ok();
+        }
+    }
+}
+
+impl<'c, W: Write> ScanlineStreamer<'c, W> {
+    /// Create a new image and start streaming rows to it, starting at the top.
+    ///
+    /// ```no_run
+    /// # use tev_client::{ScanlineStreamer, TevClient};
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// let mut streamer = ScanlineStreamer::new(&mut client, "render", 640, 480, &["R", "G", "B"])?;
+    /// for _ in 0..480 {
+    ///     let row = vec![0.5; 640 * 3];
+    ///     streamer.push_row(&row)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(
+        client: &'c mut TevClient<W>,
+        name: &str,
+        width: u32,
+        height: u32,
+        channel_names: &[&str],
+    ) -> io::Result<Self> {
+        let image = client.create_image(name, width, height, channel_names)?;
+        Ok(ScanlineStreamer { client, image, next_row: 0 })
+    }
+
+    /// Send the next row of the image, `data` contains the values of all channels interleaved.
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if all rows have already been pushed
+    /// or if `data` doesn't contain exactly one value per channel for each pixel in a row.
+    pub fn push_row(&mut self, data: &[f32]) -> io::Result<()> {
+        if self.is_complete() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("all {} rows of the image have already been pushed", self.image.height),
+            ));
+        }
+
+        self.image.update(self.client, 0, self.next_row, self.image.width, 1, data)?;
+        self.next_row += 1;
+        Ok(())
+    }
+
+    /// The number of rows that have been pushed so far, this is also the `y` coordinate of the next row.
+    pub fn rows_pushed(&self) -> u32 {
+        self.next_row
+    }
+
+    /// Whether all rows of the image have been pushed.
+    pub fn is_complete(&self) -> bool {
+        self.next_row >= self.image.height
+    }
+
+    /// The image being streamed to.
+    pub fn image(&self) -> &TevImage {
+        &self.image
+    }
+
+    /// Stop streaming, for example because rendering was interrupted, and return the number of rows that were pushed.
+    /// If `clear_remaining` is true the rows that haven't been pushed yet are set to zero in a single update,
+    /// so _tev_ doesn't keep showing rows of a previous frame below the rows of this one.
+    ///
+    /// ```no_run
+    /// # use tev_client::{ScanlineStreamer, TevClient};
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// # let interrupted = || true;
+    /// let mut streamer = ScanlineStreamer::new(&mut client, "render", 640, 480, &["R", "G", "B"])?;
+    /// while !streamer.is_complete() {
+    ///     if interrupted() {
+    ///         let rows = streamer.cancel(true)?;
+    ///         println!("rendering was interrupted after {} rows", rows);
+    ///         break;
+    ///     }
+    ///     streamer.push_row(&vec![0.5; 640 * 3])?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cancel(self, clear_remaining: bool) -> io::Result<u32> {
+        if clear_remaining && !self.is_complete() {
+            let rows = self.image.height - self.next_row;
+            let zeros = vec![0.0; self.image.width as usize * rows as usize * self.image.channel_names.len()];
+            self.image.update(self.client, 0, self.next_row, self.image.width, rows, &zeros)?;
+        }
+        Ok(self.next_row)
+    }
+}
+
+impl TevImage {
+    /// The name of this image in _tev_.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn channel_names(&self) -> &[String] {
+        &self.channel_names
+    }
+
+    /// Update the region at (`x`, `y`) with size (`width`, `height`) of this image.
+    /// `data` contains the values of all channels interleaved, in row-major order.
+    /// The components of each pixel are in the order of [TevImage::channel_names], the order the image was created with.
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if the region doesn't fit within this image,
+    /// see [UpdateImageError::OutOfBounds], or if the length of `data` doesn't match the region.
+    pub fn update<W: Write>(&self, client: &mut TevClient<W>, x: u32, y: u32, width: u32, height: u32, data: &[f32]) -> io::Result<()> {
+        if x as u64 + width as u64 > self.width as u64 || y as u64 + height as u64 > self.height as u64 {
+            let e = UpdateImageError::OutOfBounds { x, y, width, height, image_width: self.width, image_height: self.height };
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, e));
+        }
+
+        let packet = PacketUpdateImage::new(
+            &self.name,
+            client.default_grab_focus(),
+            &self.channel_names,
+            self.layout.offsets(),
+            self.layout.strides(),
+            x, y, width, height,
+            data,
+        ).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        client.send_update(packet)
+    }
+
+    /// Close this image in _tev_.
+    pub fn close<W: Write>(self, client: &mut TevClient<W>) -> io::Result<()> {
+        client.close_tracked(&self.name)
+    }
+}
+
+/// Normalize `data` to the range `0.0..=1.0` like [TevClient::update_u8], splitting the work over the available threads.
+/// The values stay in the same order as `data`.
+fn normalize_u8_parallel(data: &[u8]) -> Vec<f32> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_len = data.len().div_ceil(threads).max(1);
+
+    let mut values = vec![0.0; data.len()];
+    std::thread::scope(|scope| {
+        for (data, values) in data.chunks(chunk_len).zip(values.chunks_mut(chunk_len)) {
+            scope.spawn(move || {
+                for (value, &v) in values.iter_mut().zip(data) {
+                    *value = v as f32 / 255.0;
+                }
+            });
+        }
+    });
+    values
+}
@@ -0,0 +1,441 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use crate::{validate_update, PacketCreateImage, PacketType, PacketUpdateImage, TevPacket, TevWriter, UpdateImageError};
+
+/// The offsets and strides of each channel within a flat data buffer,
+/// the owned equivalent of [PacketUpdateImage::channel_offsets] and [PacketUpdateImage::channel_strides].
+///
+/// This is mostly useful to derive these values from the shape and strides of a multidimensional array,
+/// see [ChannelLayout::from_hwc].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ChannelLayout {
+    offsets: Vec<u64>,
+    strides: Vec<u64>,
+}
+
+/// The reason the shape and strides passed to [ChannelLayout::from_hwc] can't be represented.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum LayoutError {
+    /// The shape or strides don't have exactly 3 dimensions.
+    NotThreeDimensional { shape: usize, strides: usize },
+    /// One of the strides is negative, _tev_ can only step forward through the data.
+    NegativeStride { axis: usize },
+    /// Consecutive rows are not exactly `width` pixels apart, so pixels can't be addressed with a single stride.
+    /// This is never returned for a single column, where the row stride is used as the pixel stride.
+    RowsNotContiguous { row_stride: usize, expected: usize },
+    /// An offset or stride in elements doesn't fit in a `usize`.
+    Overflow,
+}
+
+impl ChannelLayout {
+    /// Build a layout from explicit per-channel offsets and strides.
+    pub fn new(offsets: Vec<u64>, strides: Vec<u64>) -> Self {
+        assert_eq!(offsets.len(), strides.len(), "Channel count must be consistent");
+        ChannelLayout { offsets, strides }
+    }
+
+    /// The layout of interleaved pixel data, where all channels of a pixel are stored next to each other.
+    pub fn interleaved(channel_count: usize) -> Self {
+        ChannelLayout {
+            offsets: (0..channel_count as u64).collect(),
+            strides: vec![channel_count as u64; channel_count],
+        }
+    }
+
+    /// The layout of planar pixel data, where all values of each channel are stored next to each other,
+    /// one channel after the other.
+    pub fn planar(channel_count: usize, pixel_count: u64) -> Self {
+        ChannelLayout {
+            offsets: (0..channel_count as u64).map(|c| c.saturating_mul(pixel_count)).collect(),
+            strides: vec![1; channel_count],
+        }
+    }
+
+    /// Derive the layout of an image stored as a `(height, width, channels)` array with the given element strides.
+    /// These are exactly the values returned by `shape()` and `strides()` on an `ndarray::Array3`,
+    /// so non-standard views like a subset of the channels are supported too.
+    ///
+    /// The data slice sent with this layout must start at the first element of the array. Since the strides can't be
+    /// negative that's the start of `as_slice_memory_order()` for a contiguous array, for a view into a larger array
+    /// it's the data of that array starting at the first element of the view.
+    ///
+    /// ```ignore
+    /// let layout = ChannelLayout::from_hwc(array.shape(), array.strides())?;
+    /// let (height, width) = (array.shape()[0] as u32, array.shape()[1] as u32);
+    /// client.send(PacketUpdateImage {
+    ///     image_name: "test",
+    ///     grab_focus: false,
+    ///     channel_names: &["R", "G", "B"],
+    ///     channel_offsets: layout.offsets(),
+    ///     channel_strides: layout.strides(),
+    ///     x: 0,
+    ///     y: 0,
+    ///     width,
+    ///     height,
+    ///     data: array.as_slice_memory_order().unwrap(),
+    /// })?;
+    /// ```
+    pub fn from_hwc(shape: &[usize], strides: &[isize]) -> Result<Self, LayoutError> {
+        if shape.len() != 3 || strides.len() != 3 {
+            return Err(LayoutError::NotThreeDimensional { shape: shape.len(), strides: strides.len() });
+        }
+
+        let mut unsigned_strides = [0; 3];
+        for (axis, &stride) in strides.iter().enumerate() {
+            if stride < 0 {
+                return Err(LayoutError::NegativeStride { axis });
+            }
+            unsigned_strides[axis] = stride as usize;
+        }
+        let [row_stride, pixel_stride, channel_stride] = unsigned_strides;
+
+        let (height, width, channels) = (shape[0], shape[1], shape[2]);
+        let pixel_stride = if width <= 1 {
+            // a single column has one pixel per row, so consecutive pixels are exactly one row apart
+            row_stride
+        } else {
+            // tev addresses pixels as a single linear index, so rows have to follow each other directly
+            let expected = width.checked_mul(pixel_stride).ok_or(LayoutError::Overflow)?;
+            if height > 1 && row_stride != expected {
+                return Err(LayoutError::RowsNotContiguous { row_stride, expected });
+            }
+            pixel_stride
+        };
+
+        let offsets = (0..channels)
+            .map(|c| c.checked_mul(channel_stride).map(|offset| offset as u64).ok_or(LayoutError::Overflow))
+            .collect::<Result<_, _>>()?;
+        Ok(ChannelLayout {
+            offsets,
+            strides: vec![pixel_stride as u64; channels],
+        })
+    }
+
+    /// The offset of each channel in the data.
+    pub fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+
+    /// The stride between consecutive pixels of each channel in the data.
+    pub fn strides(&self) -> &[u64] {
+        &self.strides
+    }
+
+    /// The number of channels in this layout.
+    pub fn channel_count(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// The exact data length [PacketUpdateImage] expects for `pixel_count` pixels in this layout.
+    /// This saturates at `u64::MAX` instead of overflowing, validating the update then returns [UpdateImageError::Overflow].
+    pub fn data_len(&self, pixel_count: u64) -> u64 {
+        if pixel_count == 0 {
+            return 0;
+        }
+        self.offsets.iter().zip(&self.strides)
+            .map(|(&o, &s)| o.saturating_add((pixel_count - 1).saturating_mul(s)).saturating_add(1))
+            .max().unwrap_or(0)
+    }
+}
+
+/// A [PacketUpdateImage] that owns its [ChannelLayout] but still borrows the pixel data,
+/// constructed using [PacketUpdateImage::planar] or [PacketUpdateImage::interleaved].
+#[derive(Debug)]
+pub struct PacketUpdateImageLayout<'a, S: AsRef<str> + 'a> {
+    pub image_name: &'a str,
+    pub grab_focus: bool,
+    pub channel_names: &'a [S],
+    pub layout: ChannelLayout,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub data: &'a [f32],
+}
+
+impl<'a, S: AsRef<str> + 'a> PacketUpdateImage<'a, S> {
+    /// Construct an update for tightly packed planar data, see [ChannelLayout::planar].
+    /// Returns an error if `data` doesn't contain exactly `width * height` values for each channel.
+    pub fn planar(
+        image_name: &'a str,
+        channel_names: &'a [S],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &'a [f32],
+    ) -> Result<PacketUpdateImageLayout<'a, S>, UpdateImageError> {
+        let layout = ChannelLayout::planar(channel_names.len(), width as u64 * height as u64);
+        PacketUpdateImageLayout { image_name, grab_focus: false, channel_names, layout, x, y, width, height, data }
+            .checked()
+    }
+
+    /// Construct an update for tightly packed interleaved data, see [ChannelLayout::interleaved].
+    /// Returns an error if `data` doesn't contain exactly `width * height` values for each channel.
+    pub fn interleaved(
+        image_name: &'a str,
+        channel_names: &'a [S],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &'a [f32],
+    ) -> Result<PacketUpdateImageLayout<'a, S>, UpdateImageError> {
+        let layout = ChannelLayout::interleaved(channel_names.len());
+        PacketUpdateImageLayout { image_name, grab_focus: false, channel_names, layout, x, y, width, height, data }
+            .checked()
+    }
+}
+
+impl<'a, S: AsRef<str> + 'a> PacketCreateImage<'a, S> {
+    /// Construct an update that fills the entire image this packet creates with tightly packed interleaved data,
+    /// using the same name, size and channels. See [PacketUpdateImage::interleaved] for the possible errors.
+    ///
+    /// ```no_run
+    /// # use tev_client::{TevClient, PacketCreateImage};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// let create = PacketCreateImage::builder("test", 2, 1).build();
+    /// let update = create.full_update(&[1.0, 0.0, 0.0, 0.0, 1.0, 0.0])?;
+    /// client.send(create)?;
+    /// client.send(update)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn full_update(&self, data: &'a [f32]) -> Result<PacketUpdateImageLayout<'a, S>, UpdateImageError> {
+        PacketUpdateImage::interleaved(self.image_name, self.channel_names, 0, 0, self.width, self.height, data)
+    }
+}
+
+impl<'a, S: AsRef<str> + 'a> PacketUpdateImageLayout<'a, S> {
+    /// Borrow this packet as a [PacketUpdateImage].
+    pub fn as_packet(&self) -> PacketUpdateImage<'_, S> {
+        PacketUpdateImage {
+            image_name: self.image_name,
+            grab_focus: self.grab_focus,
+            channel_names: self.channel_names,
+            channel_offsets: self.layout.offsets(),
+            channel_strides: self.layout.strides(),
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+            data: self.data,
+        }
+    }
+
+    fn checked(self) -> Result<Self, UpdateImageError> {
+        self.as_packet().validate()?;
+        Ok(self)
+    }
+}
+
+impl<'a, S: AsRef<str> + 'a> TevPacket for PacketUpdateImageLayout<'a, S> {
+    fn packet_type(&self) -> PacketType {
+        self.as_packet().packet_type()
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        self.as_packet().write_to(writer)
+    }
+}
+
+/// The parts of a [PacketUpdateImage] that stay the same when the same region is updated repeatedly,
+/// so only the pixel data has to be provided for every update.
+///
+/// ```no_run
+/// # use tev_client::{ChannelLayout, PreparedUpdate, TevClient};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut client: TevClient = unimplemented!();
+/// # let frames: Vec<Vec<f32>> = vec![];
+/// let update = PreparedUpdate::new("render", &["R", "G", "B"], ChannelLayout::interleaved(3), 0, 0, 64, 64)?;
+/// for frame in &frames {
+///     client.send(update.data(frame)?)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PreparedUpdate {
+    image_name: String,
+    grab_focus: bool,
+    channel_names: Vec<String>,
+    layout: ChannelLayout,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl PreparedUpdate {
+    /// Prepare updates of the `width * height` pixels starting at (`x`, `y`) with data in the given layout.
+    /// Returns an error if the channel names or layout are invalid, the same checks as [PacketUpdateImage::validate]
+    /// except for the data length, which is checked by [PreparedUpdate::data] instead.
+    pub fn new<S: AsRef<str>>(
+        image_name: &str,
+        channel_names: &[S],
+        layout: ChannelLayout,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, UpdateImageError> {
+        let data_len = layout.data_len(width as u64 * height as u64);
+        let data_len = usize::try_from(data_len).map_err(|_| UpdateImageError::Overflow)?;
+        validate_update(channel_names, layout.offsets(), layout.strides(), width, height, data_len)?;
+
+        Ok(PreparedUpdate {
+            image_name: image_name.to_string(),
+            grab_focus: false,
+            channel_names: channel_names.iter().map(|c| c.as_ref().to_string()).collect(),
+            layout,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Set whether the packets returned by [PreparedUpdate::data] grab focus, this is false by default.
+    pub fn grab_focus(mut self, grab_focus: bool) -> Self {
+        self.grab_focus = grab_focus;
+        self
+    }
+
+    /// The exact length the data passed to [PreparedUpdate::data] must have.
+    pub fn data_len(&self) -> u64 {
+        self.layout.data_len(self.width as u64 * self.height as u64)
+    }
+
+    /// Build the packet updating the region with `data`.
+    /// Returns [UpdateImageError::DataSizeMismatch] if `data` doesn't have exactly [PreparedUpdate::data_len] values.
+    pub fn data<'a>(&'a self, data: &'a [f32]) -> Result<PacketUpdateImage<'a, String>, UpdateImageError> {
+        let expected = self.data_len();
+        if data.len() as u64 != expected {
+            return Err(UpdateImageError::DataSizeMismatch { expected, actual: data.len() });
+        }
+
+        Ok(PacketUpdateImage {
+            image_name: &self.image_name,
+            grab_focus: self.grab_focus,
+            channel_names: &self.channel_names,
+            channel_offsets: self.layout.offsets(),
+            channel_strides: self.layout.strides(),
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+            data,
+        })
+    }
+}
+
+impl Display for LayoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutError::NotThreeDimensional { shape, strides } =>
+                write!(f, "expected 3 dimensions, got a shape with {} and strides with {}", shape, strides),
+            LayoutError::NegativeStride { axis } =>
+                write!(f, "stride of axis {} is negative", axis),
+            LayoutError::RowsNotContiguous { row_stride, expected } =>
+                write!(f, "row stride must be {} to address pixels linearly, got {}", expected, row_stride),
+            LayoutError::Overflow =>
+                write!(f, "offset or stride overflows usize"),
+        }
+    }
+}
+
+impl Error for LayoutError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ChannelLayout, LayoutError, PacketCreateImage, PacketUpdateImage, PacketUpdateImageOwned, PreparedUpdate,
+        UpdateImageError,
+    };
+
+    #[test]
+    fn from_hwc_interleaved() {
+        // a standard (2, 3, 4) array
+        let layout = ChannelLayout::from_hwc(&[2, 3, 4], &[12, 4, 1]).unwrap();
+        assert_eq!(layout, ChannelLayout::interleaved(4));
+    }
+
+    #[test]
+    fn from_hwc_planar() {
+        // a (2, 3, 4) view of a (4, 2, 3) array, with the axes permuted to height, width, channel
+        let layout = ChannelLayout::from_hwc(&[2, 3, 4], &[3, 1, 6]).unwrap();
+        assert_eq!(layout, ChannelLayout::planar(4, 6));
+    }
+
+    #[test]
+    fn from_hwc_single_column() {
+        // column 2 of a (3, 5, 2) array, the rows are not contiguous but there is only one pixel per row
+        let layout = ChannelLayout::from_hwc(&[3, 1, 2], &[10, 2, 1]).unwrap();
+        assert_eq!(layout.offsets(), &[0, 1]);
+        assert_eq!(layout.strides(), &[10, 10]);
+        assert_eq!(layout.data_len(3), 22);
+
+        assert!(ChannelLayout::from_hwc(&[3, 0, 2], &[10, 2, 1]).is_ok());
+    }
+
+    #[test]
+    fn from_hwc_errors() {
+        assert_eq!(
+            ChannelLayout::from_hwc(&[2, 3, 4], &[16, 4, 1]),
+            Err(LayoutError::RowsNotContiguous { row_stride: 16, expected: 12 }),
+        );
+        assert_eq!(
+            ChannelLayout::from_hwc(&[2, 3], &[3, 1]),
+            Err(LayoutError::NotThreeDimensional { shape: 2, strides: 2 }),
+        );
+        assert_eq!(ChannelLayout::from_hwc(&[2, 3, 4], &[12, -4, 1]), Err(LayoutError::NegativeStride { axis: 1 }));
+        assert_eq!(ChannelLayout::from_hwc(&[1, usize::MAX, 1], &[0, 2, 1]), Err(LayoutError::Overflow));
+        assert_eq!(ChannelLayout::from_hwc(&[1, 1, 4], &[0, 1, isize::MAX]), Err(LayoutError::Overflow));
+    }
+
+    #[test]
+    fn planar_and_interleaved_layouts() {
+        let data = [0.0; 12];
+
+        let planar = PacketUpdateImage::planar("test", &["R", "G", "B"], 0, 0, 2, 2, &data).unwrap();
+        assert_eq!(planar.layout.offsets(), &[0, 4, 8]);
+        assert_eq!(planar.layout.strides(), &[1, 1, 1]);
+
+        let interleaved = PacketUpdateImage::interleaved("test", &["R", "G", "B"], 0, 0, 2, 2, &data).unwrap();
+        assert_eq!(interleaved.layout.offsets(), &[0, 1, 2]);
+        assert_eq!(interleaved.layout.strides(), &[3, 3, 3]);
+
+        let result = PacketUpdateImage::planar("test", &["R", "G", "B"], 0, 0, 2, 2, &data[1..]);
+        assert_eq!(result.unwrap_err(), UpdateImageError::DataSizeMismatch { expected: 12, actual: 11 });
+    }
+
+    #[test]
+    fn prepared_update_matches_packet() {
+        let update = PreparedUpdate::new("test", &["R", "G"], ChannelLayout::planar(2, 4), 1, 2, 2, 2).unwrap();
+        assert_eq!(update.data_len(), 8);
+
+        let data = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0];
+        let expected = PacketUpdateImage::planar("test", &["R", "G"], 1, 2, 2, 2, &data).unwrap();
+        assert_eq!(
+            PacketUpdateImageOwned::from(update.data(&data).unwrap()),
+            PacketUpdateImageOwned::from(expected.as_packet()),
+        );
+
+        let short = update.data(&data[1..]).unwrap_err();
+        assert_eq!(short, UpdateImageError::DataSizeMismatch { expected: 8, actual: 7 });
+    }
+
+    #[test]
+    fn full_update_from_create() {
+        let create = PacketCreateImage::builder("test", 2, 2).channels(&["R", "G"]).build();
+        let update = create.full_update(&[0.0; 8]).unwrap();
+        assert_eq!((update.image_name, update.x, update.y, update.width, update.height), ("test", 0, 0, 2, 2));
+        assert_eq!(update.channel_names, &["R", "G"]);
+        assert_eq!(update.layout, ChannelLayout::interleaved(2));
+
+        assert!(create.full_update(&[0.0; 4]).is_err());
+    }
+}
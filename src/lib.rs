@@ -7,10 +7,21 @@
 //! * [PacketCloseImage](crate::PacketCloseImage) close an opened image
 //! * [PacketCreateImage](crate::PacketCreateImage) create a new black image with given size and channels
 //! * [PacketUpdateImage](crate::PacketUpdateImage) update part of the pixels of an opened image
+//! * [PacketVectorGraphics](crate::PacketVectorGraphics) draw vector graphics on top of an opened image
+//!
+//! Each packet also has an owned version, for example [PacketCreateImageOwned](crate::PacketCreateImageOwned),
+//! which can be stored or sent to other threads more easily.
+//!
+//! Enable the `tokio` feature for an asynchronous client, see `TevClientAsync`.
+//! Enable the `test-util` feature for a fake _tev_ instance to test against, see `test_util::MockTev`.
+//! Enable the `socket2` feature to tune socket options, see `TevClient::set_send_buffer_size` and `TevClient::connect_with`.
+//! Enable the `bytemuck` feature to copy pixel data in bulk on little-endian targets, which speeds up large updates.
+//! Enable the `socks5` feature to connect to a remote _tev_ through a SOCKS5 proxy, see `TevClient::connect_via_socks5`.
+//! Enable the `tracing` feature to log spawning _tev_ and every packet sent with the `tracing` crate.
 //!
 //! ## Example code:
 //!
-//! ```rust
+//! ```no_run
 //! use tev_client::{TevClient, TevError, PacketCreateImage};
 //!
 //! fn main() -> Result<(), TevError> {
@@ -31,21 +42,93 @@
 //! }
 //! ```
 
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::io;
-use std::io::{BufRead, BufReader, Write};
-use std::net::TcpStream;
-use std::process::{Command, Stdio};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::time::Duration;
+
+use image::TrackedImage;
+
+pub use builder::TevClientBuilder;
+pub use colormap::Colormap;
+pub use delta::DeltaUpdater;
+pub use image::{DiffMode, ImageData, OpenImageGuard, ScanlineStreamer, TevImage};
+pub use layout::{ChannelLayout, LayoutError, PacketUpdateImageLayout, PreparedUpdate};
+pub use multicast::TevMulticast;
+pub use owned::{
+    PacketCloseImageOwned, PacketCreateImageOwned, PacketOpenImageOwned, PacketReloadImageOwned, PacketUpdateImageOwned,
+    PacketVectorGraphicsOwned,
+};
+pub use protocol::PacketType;
+pub use sender::TevSender;
+pub use shared::SharedTevClient;
+
+#[cfg(feature = "tokio")]
+pub use async_client::TevClientAsync;
+
+mod builder;
+pub mod color;
+mod colormap;
+mod delta;
+mod image;
+mod layout;
+mod multicast;
+mod owned;
+pub mod protocol;
+mod sender;
+mod shared;
+
+#[cfg(feature = "tokio")]
+mod async_client;
+
+#[cfg(feature = "socks5")]
+mod socks5;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
 /// A connection to a Tev instance.
 /// Constructed using [TevClient::wrap], [TevClient::spawn] or [TevClient::spawn_path_default].
 /// Use [TevClient::send] to send commands.
-#[derive(Debug)]
-pub struct TevClient {
-    socket: TcpStream,
+///
+/// By default the client writes to a [TcpStream], but any [Write] implementation can be used with
+/// [TevClient::from_writer], for example a Unix domain socket or a `Vec<u8>` to capture the packets.
+pub struct TevClient<W: Write = TcpStream> {
+    socket: W,
+    reported_host: Option<String>,
+    connection_kind: ConnectionKind,
+    buffer: Vec<u8>,
+    /// The command _tev_ was spawned with, used to spawn it again by [TevClient::reconnect_or_respawn].
+    command: Option<Command>,
+    /// Whether [TevClient::reconnect] spawns _tev_ again, see [TevClient::with_reconnect].
+    respawn_on_reconnect: bool,
+    /// Called when a write fails because the connection broke, only set for TCP clients that can reconnect.
+    reconnect_hook: Option<ReconnectHook<W>>,
+    /// The _tev_ process, if it was spawned by this client.
+    child: Option<Child>,
+    /// The rest of the stdout of [TevClient::child], see [TevClient::take_stdout].
+    stdout: Option<BufReader<ChildStdout>>,
+    default_grab_focus: bool,
+    protocol: TevProtocol,
+    flush_after_send: bool,
+    /// The images opened or created by the high-level helpers, in order.
+    images: Vec<TrackedImage>,
+    /// The number of bytes of an interrupted write that were sent anyway, see [TevClient::is_desynchronized].
+    desynchronized: Option<usize>,
+    /// The largest packet that may be sent, see [TevClient::set_max_packet_size].
+    max_packet_size: Option<usize>,
 }
 
+type ReconnectHook<W> = fn(&mut TevClient<W>) -> Result<(), TevError>;
+
 /// The error type returned by [TevClient::spawn] in case of an error.
 ///
 /// For convenience, this type implements `From<std::io::Error>` so the errors returned by [TevClient::send]
@@ -59,14 +142,29 @@ pub enum TevError {
     /// Tev didn't respond with an address to connect to on stdout.
     /// `read` is the data that was read before stdout closed.
     NoSocketResponse { read: String },
+    /// The same as [TevError::NoSocketResponse], but also includes everything _tev_ wrote to stderr.
+    /// Only returned by [TevClient::spawn_capture_stderr].
+    StartupFailed { read: String, stderr: String },
     /// There was an error opening or writing to the TCP connection.
     /// `host` is the address received from _tev_ we're trying to connect to.
     TcpConnect { host: String, io: std::io::Error },
+    /// The TCP connection to `host` could not be established within the given timeout.
+    Timeout { host: String },
+    /// The address _tev_ reported on stdout is not of the form `host:port`, so no connection was attempted.
+    InvalidHost { host: String },
+    /// A previous write failed after sending only `written` bytes of it, so _tev_ would misinterpret everything sent
+    /// afterwards. This is wrapped in an [io::Error] with kind [io::ErrorKind::Other] when returned by
+    /// [TevClient::send], see [TevClient::is_desynchronized].
+    Desynchronized { written: usize },
+    /// A serialized packet is `len` bytes long, which is more than the `max` bytes allowed. That's either the largest
+    /// length that fits in the 32-bit length prefix _tev_ expects, or the limit set by [TevClient::set_max_packet_size].
+    /// This is wrapped in an [io::Error] with kind [io::ErrorKind::InvalidInput] when returned by [TevClient::send].
+    PacketTooLarge { len: usize, max: usize },
     /// There was some other IO error. This variant exits to make the `?` more convenient to use.
     IO { io: std::io::Error },
 }
 
-impl TevClient {
+impl TevClient<TcpStream> {
     /// Create a [TevClient] from an existing [TcpStream] that's connected to _tev_. If _tev_ may not be running yet use
     /// [TevClient::spawn] or [TevClient::spawn_path_default] instead.
     ///
@@ -79,18 +177,118 @@ impl TevClient {
     /// # Ok(())
     /// # }
     /// ```
+    ///
+    /// This enables `TCP_NODELAY` on the socket, since every packet is written in one go and small packets should
+    /// not be delayed by Nagle's algorithm. Use [TevClient::set_nodelay] to change this again.
     pub fn wrap(socket: TcpStream) -> Self {
-        TevClient { socket }
+        // failing to set this only affects latency, so it's not worth making wrap fallible for
+        socket.set_nodelay(true).ok();
+        TevClient::from_writer(socket)
+    }
+
+    /// The same as [TevClient::wrap], but first check that the other side of `socket` plausibly is _tev_.
+    ///
+    /// _tev_ never replies to anything, so there is no real handshake. Instead this returns [TevError::TcpConnect] if
+    /// the socket is not connected, if the other side already closed the connection, or if it has already sent data,
+    /// which _tev_ never does but many other servers do. This catches connecting to the wrong port early,
+    /// but not every other program listening on it.
+    pub fn wrap_checked(socket: TcpStream) -> Result<TevClient, TevError> {
+        let host = match socket.peer_addr() {
+            Ok(addr) => addr.to_string(),
+            Err(io) => return Err(TevError::TcpConnect { host: String::new(), io }),
+        };
+
+        socket.set_nonblocking(true)?;
+        let mut byte = [0];
+        let peeked = socket.peek(&mut byte);
+        socket.set_nonblocking(false)?;
+
+        let io = match peeked {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(TevClient::wrap(socket)),
+            Err(io) => io,
+            Ok(0) => io::Error::new(io::ErrorKind::ConnectionAborted, "connection was closed by the other side"),
+            Ok(_) => io::Error::new(io::ErrorKind::InvalidData, "the other side sent data, tev never does"),
+        };
+        Err(TevError::TcpConnect { host, io })
+    }
+
+    /// Create a [TevClient] by connecting to a _tev_ instance that's already running at `host`,
+    /// giving up with [TevError::Timeout] if the connection can't be established within `timeout`.
+    ///
+    /// ```no_run
+    /// # use tev_client::{TevClient, TevError};
+    /// # use std::time::Duration;
+    /// # fn main() -> Result<(), TevError> {
+    /// let mut client = TevClient::connect_timeout("127.0.0.1:14158", Duration::from_secs(1))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connect_timeout(host: &str, timeout: Duration) -> Result<TevClient, TevError> {
+        let socket = connect_host_timeout(host, AddressFamily::Any, timeout)?;
+        Ok(TevClient::wrap(socket))
+    }
+
+    /// Create a [TevClient] by connecting to a _tev_ instance at `host` that may still be starting up.
+    /// Connecting is attempted up to `attempts` times, waiting `interval` between consecutive attempts.
+    /// If none of them succeed the error of the last attempt is returned.
+    ///
+    /// ```no_run
+    /// # use tev_client::{TevClient, TevError};
+    /// # use std::time::Duration;
+    /// # fn main() -> Result<(), TevError> {
+    /// let mut client = TevClient::connect_retry("127.0.0.1:14158", 20, Duration::from_millis(500))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `attempts` is zero.
+    pub fn connect_retry(host: &str, attempts: u32, interval: Duration) -> Result<TevClient, TevError> {
+        assert_ne!(attempts, 0, "Must attempt to connect at least once");
+
+        let mut attempt = 1;
+        loop {
+            match connect_host_timeout(host, AddressFamily::Any, DEFAULT_CONNECT_TIMEOUT) {
+                Ok(socket) => return Ok(TevClient::wrap(socket)),
+                Err(e) if attempt == attempts => return Err(e),
+                Err(_) => {}
+            }
+
+            std::thread::sleep(interval);
+            attempt += 1;
+        }
     }
 
     /// Create a new [TevClient] by spawning _tev_ assuming it is in `PATH` with the default hostname.
+    /// Use [TevClient::builder] for more configuration options.
     pub fn spawn_path_default() -> Result<TevClient, TevError> {
-        TevClient::spawn(Command::new("tev"))
+        TevClient::builder().spawn()
+    }
+
+    /// Create a new [TevClient] by spawning _tev_ assuming it is in `PATH`, listening on `hostname` instead of the default.
+    ///
+    /// ```no_run
+    /// # use tev_client::{TevClient, TevError};
+    /// # fn main() -> Result<(), TevError> {
+    /// let mut client = TevClient::spawn_path("127.0.0.1:14159")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_path(hostname: &str) -> Result<TevClient, TevError> {
+        TevClient::builder().hostname(hostname).spawn()
     }
 
     /// Crate a [TevClient] from a command that spawns _tev_.
     /// If _tev_ is in `PATH` and the default hostname should be used use [TevClient::spawn_path_default] instead.
     ///
+    /// Connecting to the address _tev_ reports times out after [DEFAULT_CONNECT_TIMEOUT],
+    /// use [TevClient::spawn_with_timeout] to choose a different timeout.
+    ///
+    /// This returns as soon as the connection is made, which can be before the _tev_ window has finished starting.
+    /// _tev_ never sends anything back over the connection, so there is no way to confirm that it's ready
+    /// or that a packet was processed. If packets sent right after spawning go missing with some version of _tev_,
+    /// the only workaround is to wait a moment before sending them, or to send them again.
+    ///
     /// ```no_run
     /// # use tev_client::{TevClient, TevError};
     /// # use std::process::Command;
@@ -101,296 +299,3170 @@ impl TevClient {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn spawn(mut command: Command) -> Result<TevClient, TevError> {
-        const PATTERNS: &[&str] = &[
-            "Initialized IPC, listening on ",
-            "Connected to primary instance at ",
-        ];
+    pub fn spawn(command: Command) -> Result<TevClient, TevError> {
+        TevClient::spawn_with_timeout(command, DEFAULT_CONNECT_TIMEOUT)
+    }
+
+    /// The same as [TevClient::spawn], except that connecting to the address _tev_ reports gives up with
+    /// [TevError::Timeout] after `timeout`. Note that the time spent waiting for _tev_ to start is not limited.
+    pub fn spawn_with_timeout(mut command: Command, timeout: Duration) -> Result<TevClient, TevError> {
+        let spawned = spawn_connect(&mut command, DEFAULT_HOST_PATTERNS, AddressFamily::Any, timeout, None)?;
+        Ok(TevClient::spawned(spawned, command))
+    }
 
-        let mut child = command.stdout(Stdio::piped()).spawn()
-            .map_err(|io| TevError::Command { io })?;
-        let reader = BufReader::new(child.stdout.take().unwrap());
+    /// The same as [TevClient::spawn], except that the address is found on stdout using `patterns` instead of
+    /// [DEFAULT_HOST_PATTERNS]. The address is taken to be the rest of the first line containing one of the patterns.
+    ///
+    /// ```no_run
+    /// # use tev_client::{TevClient, TevError};
+    /// # use std::process::Command;
+    /// # fn main() -> Result<(), TevError> {
+    /// let mut client = TevClient::spawn_with_patterns(Command::new("tev-fork"), &["IPC ready at "])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_with_patterns(mut command: Command, patterns: &[&str]) -> Result<TevClient, TevError> {
+        let spawned = spawn_connect(&mut command, patterns, AddressFamily::Any, DEFAULT_CONNECT_TIMEOUT, None)?;
+        Ok(TevClient::spawned(spawned, command))
+    }
 
-        let mut read = String::new();
-        for line in reader.lines() {
-            let line = line.map_err(|io| TevError::Stdout { io })?;
+    /// The same as [TevClient::spawn], except that only the addresses allowed by `family` are tried, in the order it
+    /// prefers, when connecting to the host _tev_ reports.
+    pub fn spawn_with_address_family(mut command: Command, family: AddressFamily) -> Result<TevClient, TevError> {
+        let spawned = spawn_connect(&mut command, DEFAULT_HOST_PATTERNS, family, DEFAULT_CONNECT_TIMEOUT, None)?;
+        Ok(TevClient::spawned(spawned, command))
+    }
 
-            for pattern in PATTERNS {
-                if let Some(start) = line.find(pattern) {
-                    let rest = &line[start + pattern.len()..];
+    /// The same as [TevClient::spawn], except that only an address with the given `port` is accepted.
+    /// Lines reporting any other address are ignored, for example when the output of multiple _tev_ instances
+    /// ends up in the same stream. If no matching address is reported [TevError::NoSocketResponse] is returned.
+    ///
+    /// ```no_run
+    /// # use tev_client::{TevClient, TevError};
+    /// # use std::process::Command;
+    /// # fn main() -> Result<(), TevError> {
+    /// let mut command = Command::new("tev");
+    /// command.arg("--hostname=127.0.0.1:14159");
+    /// let mut client = TevClient::spawn_expecting_port(command, 14159)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_expecting_port(mut command: Command, port: u16) -> Result<TevClient, TevError> {
+        let port = Some(port);
+        let spawned = spawn_connect(&mut command, DEFAULT_HOST_PATTERNS, AddressFamily::Any, DEFAULT_CONNECT_TIMEOUT, port)?;
+        Ok(TevClient::spawned(spawned, command))
+    }
 
-                    // cut of any trailing terminal escape codes
-                    let end = rest.find('\u{1b}').unwrap_or(rest.len());
-                    let host = &rest[..end];
+    /// The same as [TevClient::spawn], except that the stderr of _tev_ is captured instead of inherited.
+    /// If _tev_ exits without reporting an address, the returned [TevError::StartupFailed] includes its stderr.
+    ///
+    /// Note that [TevClient::spawn] and the other constructors only redirect stdout, so any other stderr setting of
+    /// `command` is respected. If _tev_ starts successfully its stderr is available through
+    /// the `stderr` field of [TevClient::child_mut], and it should be read or dropped since _tev_ blocks once the
+    /// pipe buffer is full.
+    pub fn spawn_capture_stderr(mut command: Command) -> Result<TevClient, TevError> {
+        command.stderr(Stdio::piped());
+        let spawned = spawn_connect(&mut command, DEFAULT_HOST_PATTERNS, AddressFamily::Any, DEFAULT_CONNECT_TIMEOUT, None)?;
+        Ok(TevClient::spawned(spawned, command))
+    }
 
-                    let socket = TcpStream::connect(host)
-                        .map_err(|io| TevError::TcpConnect { host: host.to_string(), io })?;
-                    return Ok(TevClient::wrap(socket));
-                }
-            }
+    /// The same as [TevClient::spawn], except that `command` is kept around to spawn _tev_ again if the connection
+    /// is lost, for example because the user closed _tev_.
+    ///
+    /// When a send fails because the connection was broken, [TevClient::reconnect] is called and the send is retried
+    /// once. If reconnecting fails too, the returned [io::Error] wraps the [TevError] describing why.
+    /// Note that the operating system typically only reports a broken connection on the write after the one that
+    /// actually got lost, so that packet is not retried.
+    pub fn with_reconnect(mut command: Command) -> Result<TevClient, TevError> {
+        let spawned = spawn_connect(&mut command, DEFAULT_HOST_PATTERNS, AddressFamily::Any, DEFAULT_CONNECT_TIMEOUT, None)?;
+        let mut client = TevClient::spawned(spawned, command);
+        client.respawn_on_reconnect = true;
+        client.reconnect_hook = Some(TevClient::reconnect);
+        Ok(client)
+    }
 
-            read.push_str(&line);
-            read.push('\n');
-        }
+    /// Create a client for a _tev_ process that was just spawned by `command`.
+    fn spawned(spawned: Spawned, command: Command) -> TevClient {
+        let mut client = TevClient::wrap(spawned.socket);
+        client.reported_host = Some(spawned.host);
+        client.connection_kind = spawned.kind;
+        client.child = Some(spawned.child);
+        client.stdout = Some(spawned.stdout);
+        client.command = Some(command);
+        client
+    }
 
-        Err(TevError::NoSocketResponse { read })
+    /// Replace the connection to _tev_ with a new one.
+    ///
+    /// If this client was created with [TevClient::with_reconnect] _tev_ is spawned again,
+    /// otherwise this connects to the host _tev_ originally reported or to the address of the current connection.
+    pub fn reconnect(&mut self) -> Result<(), TevError> {
+        let socket = if self.respawn_on_reconnect {
+            self.respawn()?
+        } else {
+            self.connect_again()?
+        };
+        self.replace_socket(socket);
+        Ok(())
     }
 
-    /// Send a command to _tev_. A command is any struct in this crate that implements [TevPacket].
-    /// # Example
+    /// Replace the connection to _tev_ with a new one, spawning _tev_ again only if it's no longer running.
+    ///
+    /// This first connects to the host _tev_ originally reported or to the address of the current connection,
+    /// like [TevClient::reconnect] does without [TevClient::with_reconnect]. If that fails and this client spawned
+    /// _tev_ itself, the same command is run again. When another _tev_ instance is already running that command
+    /// reports "Connected to primary instance at", and the new connection goes to that primary instance instead.
+    ///
+    /// Respawning uses [DEFAULT_HOST_PATTERNS] and [DEFAULT_CONNECT_TIMEOUT],
+    /// even if the client was originally spawned with different ones.
+    ///
     /// ```no_run
-    /// # use tev_client::{TevClient, PacketOpenImage};
-    /// # fn main() -> std::io::Result<()> {
-    /// # use tev_client::PacketCloseImage;
-    /// # let mut client: TevClient = unimplemented!();
-    /// client.send(PacketCloseImage { image_name: "test.exf" })?;
+    /// # use tev_client::{TevClient, TevError, PacketCloseImage};
+    /// # fn main() -> Result<(), TevError> {
+    /// let mut client = TevClient::spawn_path_default()?;
+    /// // ... the user closes tev, or restarts it
+    /// if client.send(PacketCloseImage { image_name: "test.exr" }).is_err() {
+    ///     client.reconnect_or_respawn()?;
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    pub fn send(&mut self, packet: impl TevPacket) -> io::Result<()> {
-        //reserve space for the packet length
-        let vec = vec![0, 0, 0, 0];
+    pub fn reconnect_or_respawn(&mut self) -> Result<(), TevError> {
+        let socket = match self.connect_again() {
+            Ok(socket) => socket,
+            Err(_) if self.command.is_some() => self.respawn()?,
+            Err(e) => return Err(e),
+        };
+        self.replace_socket(socket);
+        Ok(())
+    }
 
-        //append the packet
-        let mut target = TevWriter { target: vec };
-        packet.write_to(&mut target);
-        let mut vec = target.target;
+    /// Connect to the _tev_ instance this client was connected to before.
+    fn connect_again(&self) -> Result<TcpStream, TevError> {
+        let host = match &self.reported_host {
+            Some(host) => host.clone(),
+            None => self.socket.peer_addr()?.to_string(),
+        };
+        connect_host_timeout(&host, AddressFamily::Any, DEFAULT_CONNECT_TIMEOUT)
+    }
 
-        //actually fill in the packet length
-        let packet_length = vec.len() as u32;
-        vec[0..4].copy_from_slice(&packet_length.to_le_bytes());
+    /// Spawn _tev_ again with the command it was originally spawned with, which must be set.
+    fn respawn(&mut self) -> Result<TcpStream, TevError> {
+        let command = self.command.as_mut().expect("only clients that spawned tev can respawn it");
+        let spawned = spawn_connect(command, DEFAULT_HOST_PATTERNS, AddressFamily::Any, DEFAULT_CONNECT_TIMEOUT, None)?;
+        self.reported_host = Some(spawned.host);
+        self.connection_kind = spawned.kind;
+        self.child = Some(spawned.child);
+        self.stdout = Some(spawned.stdout);
+        if spawned.kind == ConnectionKind::NewInstance {
+            self.images.clear();
+        }
+        Ok(spawned.socket)
+    }
 
-        self.socket.write_all(&vec)
+    fn replace_socket(&mut self, socket: TcpStream) {
+        let nodelay = self.socket.nodelay().unwrap_or(true);
+        socket.set_nodelay(nodelay).ok();
+        self.socket = socket;
+        self.desynchronized = None;
     }
-}
 
-/// Opens a new image where `image_name` is the path.
-#[derive(Debug)]
-pub struct PacketOpenImage<'a> {
-    pub image_name: &'a str,
-    pub grab_focus: bool,
-    pub channel_selector: &'a str,
-}
+    /// Set the `TCP_NODELAY` option on the underlying socket, see [TcpStream::set_nodelay].
+    /// This is enabled by default by all constructors.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.socket.set_nodelay(nodelay)
+    }
 
-impl TevPacket for PacketOpenImage<'_> {
-    fn write_to(&self, writer: &mut TevWriter) {
-        writer.write(PacketType::OpenImageV2);
-        writer.write(self.grab_focus);
-        writer.write(self.image_name);
-        writer.write(self.channel_selector);
+    /// Set a timeout for writing to the socket, see [TcpStream::set_write_timeout].
+    /// If sending a packet takes longer, for example because _tev_ is stuck, the send fails with
+    /// [io::ErrorKind::TimedOut] on all platforms.
+    ///
+    /// The packet may have been partially written at that point, so the connection should not be used any more.
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.socket.set_write_timeout(timeout)
     }
-}
 
-/// Reload an existing image with name or path `image_name` from disk.
-#[derive(Debug)]
-pub struct PacketReloadImage<'a> {
-    pub image_name: &'a str,
-    pub grab_focus: bool,
-}
+    /// The timeout set by [TevClient::set_write_timeout].
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        self.socket.write_timeout()
+    }
 
-impl TevPacket for PacketReloadImage<'_> {
-    fn write_to(&self, writer: &mut TevWriter) {
-        writer.write(PacketType::ReloadImage);
-        writer.write(self.grab_focus);
-        writer.write(self.image_name);
+    /// Set the size of the send buffer of the socket (`SO_SNDBUF`), a larger buffer can improve throughput when
+    /// sending large updates over a high-latency network. The operating system may adjust the requested size.
+    ///
+    /// This is only available with the `socket2` feature.
+    #[cfg(feature = "socket2")]
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        socket2::SockRef::from(&self.socket).set_send_buffer_size(size)
     }
-}
 
-/// Update part of an existing image with new pixel data.
-#[derive(Debug)]
-pub struct PacketUpdateImage<'a, S: AsRef<str> + 'a> {
-    pub image_name: &'a str,
-    pub grab_focus: bool,
-    pub channel_names: &'a [S],
-    pub channel_offsets: &'a [u64],
-    pub channel_strides: &'a [u64],
-    pub x: u32,
-    pub y: u32,
-    pub width: u32,
-    pub height: u32,
-    pub data: &'a [f32],
-}
+    /// The size of the send buffer of the socket, see [TevClient::set_send_buffer_size].
+    ///
+    /// This is only available with the `socket2` feature.
+    #[cfg(feature = "socket2")]
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        socket2::SockRef::from(&self.socket).send_buffer_size()
+    }
 
-impl<'a, S: AsRef<str> + 'a> TevPacket for PacketUpdateImage<'a, S> {
-    fn write_to(&self, writer: &mut TevWriter) {
-        let channel_count = self.channel_names.len();
+    /// Create a [TevClient] by connecting to a _tev_ instance at `addr`, with socket options that have to be set before
+    /// connecting. This is mostly useful for test harnesses that open and close many connections in quick succession.
+    /// Connecting times out after [DEFAULT_CONNECT_TIMEOUT], just like [TevClient::spawn].
+    ///
+    /// ```no_run
+    /// # use tev_client::{SocketConfig, TevClient, TevError};
+    /// # fn main() -> Result<(), TevError> {
+    /// let config = SocketConfig {
+    ///     reuse_address: true,
+    ///     local_addr: Some("127.0.0.1:14200".parse().unwrap()),
+    ///     ..SocketConfig::default()
+    /// };
+    /// let mut client = TevClient::connect_with("127.0.0.1:14158".parse().unwrap(), &config)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// This is only available with the `socket2` feature.
+    #[cfg(feature = "socket2")]
+    pub fn connect_with(addr: SocketAddr, config: &SocketConfig) -> Result<TevClient, TevError> {
+        use socket2::{Domain, Protocol, Socket, Type};
 
-        assert_ne!(channel_count, 0, "Must update at least one channel");
-        assert_eq!(channel_count, self.channel_offsets.len(), "Channel count must be consistent");
-        assert_eq!(channel_count, self.channel_strides.len(), "Channel count must be consistent");
+        let host = addr.to_string();
+        let tcp_error = |io| TevError::TcpConnect { host: host.clone(), io };
+
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP)).map_err(tcp_error)?;
+        socket.set_reuse_address(config.reuse_address).map_err(tcp_error)?;
+        if config.reuse_port {
+            #[cfg(unix)]
+            socket.set_reuse_port(true).map_err(tcp_error)?;
+            #[cfg(not(unix))]
+            return Err(tcp_error(io::Error::new(io::ErrorKind::Unsupported, "SO_REUSEPORT is only supported on unix")));
+        }
+        if let Some(size) = config.send_buffer_size {
+            socket.set_send_buffer_size(size).map_err(tcp_error)?;
+        }
+        if let Some(local_addr) = config.local_addr {
+            socket.bind(&local_addr.into()).map_err(tcp_error)?;
+        }
 
-        let pixel_count = (self.width as u64) * (self.height as u64);
-        assert_ne!(pixel_count, 0, "Must update at least one pixel");
+        match socket.connect_timeout(&addr.into(), DEFAULT_CONNECT_TIMEOUT) {
+            Ok(()) => Ok(TevClient::wrap(socket.into())),
+            Err(io) if io.kind() == io::ErrorKind::TimedOut => Err(TevError::Timeout { host }),
+            Err(io) => Err(tcp_error(io)),
+        }
+    }
 
-        let max_data_index_used = self.channel_offsets.iter().zip(self.channel_strides)
-            .map(|(&o, &s)| o + (pixel_count - 1) * s)
-            .max().unwrap();
-        assert_eq!(max_data_index_used + 1, self.data.len() as u64, "Data size does not match actually used data range");
+    /// Check whether _tev_ still seems to be listening on the other side of the connection, without sending anything.
+    ///
+    /// _tev_ doesn't have a no-op command, so this does a non-blocking peek on the socket instead.
+    /// This detects a connection that was closed by _tev_, but a connection that silently broke may still appear alive.
+    pub fn is_connected(&self) -> bool {
+        if self.socket.set_nonblocking(true).is_err() {
+            return false;
+        }
 
-        writer.write(PacketType::UpdateImageV3);
-        writer.write(self.grab_focus);
-        writer.write(self.image_name);
-        writer.write(channel_count as u32);
-        writer.write_all(self.channel_names.iter().map(AsRef::as_ref));
-        writer.write(self.x);
-        writer.write(self.y);
-        writer.write(self.width);
-        writer.write(self.height);
-        writer.write_all(self.channel_offsets);
-        writer.write_all(self.channel_strides);
+        let mut byte = [0];
+        let connected = match self.socket.peek(&mut byte) {
+            // tev closed its end of the connection
+            Ok(0) => false,
+            // tev never sends anything, but if it does that's clearly not a closed connection
+            Ok(_) => true,
+            Err(e) => e.kind() == io::ErrorKind::WouldBlock,
+        };
 
-        writer.write_all(self.data)
+        self.socket.set_nonblocking(false).is_ok() && connected
     }
-}
-
-/// Close an image.
-#[derive(Debug)]
-pub struct PacketCloseImage<'a> {
-    pub image_name: &'a str,
-}
 
-impl TevPacket for PacketCloseImage<'_> {
-    fn write_to(&self, writer: &mut TevWriter) {
-        writer.write(PacketType::CloseImage);
-        writer.write(self.image_name);
+    /// The address of the _tev_ instance this client is connected to.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.peer_addr()
     }
-}
 
-/// Create a new image with name `image_name`, size (`width`, `height`) and channels `channel_names`.
-#[derive(Debug)]
-pub struct PacketCreateImage<'a, S: AsRef<str> + 'a> {
-    pub image_name: &'a str,
-    pub grab_focus: bool,
-    pub width: u32,
-    pub height: u32,
-    pub channel_names: &'a [S],
 }
 
-impl<'a, S: AsRef<str> + 'a> TevPacket for PacketCreateImage<'a, S> {
-    fn write_to(&self, writer: &mut TevWriter) {
-        writer.write(PacketType::CreateImage);
-        writer.write(self.grab_focus);
-        writer.write(self.image_name);
-        writer.write(self.width);
-        writer.write(self.height);
-        writer.write(self.channel_names.len() as u32);
-        writer.write_all(self.channel_names.iter().map(AsRef::as_ref));
+#[cfg(unix)]
+impl TevClient<UnixStream> {
+    /// Create a [TevClient] from an existing [UnixStream] that's connected to _tev_.
+    /// This is the same as [TevClient::from_writer], it only exists for discoverability.
+    pub fn wrap_unix(socket: UnixStream) -> Self {
+        TevClient::from_writer(socket)
     }
-}
 
-/// A buffer used to construct TCP packets. For internal use only.
-#[doc(hidden)]
-pub struct TevWriter {
-    target: Vec<u8>,
+    /// Create a [TevClient] by connecting to a _tev_ instance listening on the Unix domain socket at `path`.
+    pub fn connect_unix(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(TevClient::wrap_unix(UnixStream::connect(path)?))
+    }
 }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone)]
-enum PacketType {
-    ReloadImage = 1,
-    CloseImage = 2,
-    CreateImage = 4,
-    UpdateImageV3 = 6,
-    OpenImageV2 = 7,
-}
+impl<W: Write> TevClient<W> {
+    /// Create a [TevClient] that writes packets to an arbitrary writer instead of a socket.
+    /// This is useful for debugging and testing, to see exactly what would be sent to _tev_.
+    ///
+    /// ```
+    /// # use tev_client::{TevClient, PacketCloseImage};
+    /// # fn main() -> std::io::Result<()> {
+    /// let mut client = TevClient::from_writer(Vec::new());
+    /// client.send(PacketCloseImage { image_name: "test.exr" })?;
+    /// let bytes: Vec<u8> = client.into_inner();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_writer(writer: W) -> Self {
+        TevClient {
+            socket: writer,
+            reported_host: None,
+            connection_kind: ConnectionKind::Unknown,
+            buffer: Vec::new(),
+            command: None,
+            respawn_on_reconnect: false,
+            reconnect_hook: None,
+            child: None,
+            stdout: None,
+            default_grab_focus: false,
+            protocol: TevProtocol::Current,
+            flush_after_send: false,
+            images: Vec::new(),
+            desynchronized: None,
+            max_packet_size: None,
+        }
+    }
 
-impl TevWriter {
-    fn write(&mut self, value: impl TevWritable) {
-        value.write_to(self);
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.socket
     }
 
-    fn write_all(&mut self, values: impl IntoIterator<Item=impl TevWritable>) {
-        for value in values {
-            value.write_to(self);
-        }
+    /// Get a mutable reference to the underlying writer.
+    /// Writing to it directly will probably corrupt the stream of packets.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.socket
     }
-}
 
-/// The trait implemented by all packets.
-#[doc(hidden)]
-pub trait TevPacket {
-    fn write_to(&self, writer: &mut TevWriter);
-}
+    /// Unwrap this client, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.socket
+    }
 
-trait TevWritable {
-    fn write_to(self, writer: &mut TevWriter);
-}
+    /// Set whether the high-level helpers like [TevClient::create_image], [TevClient::show_image] and
+    /// [TevImage::update] make _tev_ focus the image they affect. This is `false` by default.
+    ///
+    /// Packets sent directly always use their own `grab_focus` field, the builders default it to `false` too.
+    pub fn set_default_grab_focus(&mut self, grab_focus: bool) {
+        self.default_grab_focus = grab_focus;
+    }
 
-impl<T: TevWritable + Copy> TevWritable for &T {
-    fn write_to(self, writer: &mut TevWriter) {
-        (*self).write_to(writer);
+    /// Whether the high-level helpers grab focus, see [TevClient::set_default_grab_focus].
+    pub fn default_grab_focus(&self) -> bool {
+        self.default_grab_focus
     }
-}
 
-impl TevWritable for bool {
-    fn write_to(self, writer: &mut TevWriter) {
-        writer.target.push(self as u8);
+    /// Set which packet versions [TevClient::send_open], [TevClient::send_update] and the high-level helpers like
+    /// [TevClient::show_image] and [TevImage::update] use, see [TevProtocol]. This is [TevProtocol::Current] by default.
+    ///
+    /// Packets sent directly with [TevClient::send] are always sent as they are.
+    pub fn set_protocol_version(&mut self, protocol: TevProtocol) {
+        self.protocol = protocol;
     }
-}
 
-impl TevWritable for PacketType {
-    fn write_to(self, writer: &mut TevWriter) {
-        writer.target.push(self as u8);
+    /// The packet versions used by the high-level helpers, see [TevClient::set_protocol_version].
+    pub fn protocol_version(&self) -> TevProtocol {
+        self.protocol
     }
-}
 
-impl TevWritable for u32 {
-    fn write_to(self, writer: &mut TevWriter) {
-        writer.target.extend_from_slice(&self.to_le_bytes());
+    /// Set whether the writer is flushed after every send. This is `false` by default.
+    ///
+    /// Sockets don't buffer anything themselves so this only matters for buffered writers like [std::io::BufWriter].
+    /// For interactive use enable this so every packet reaches _tev_ immediately,
+    /// for batch use leave it disabled and call [TevClient::flush] manually.
+    pub fn set_flush_after_send(&mut self, flush: bool) {
+        self.flush_after_send = flush;
     }
-}
 
-impl TevWritable for u64 {
-    fn write_to(self, writer: &mut TevWriter) {
-        writer.target.extend_from_slice(&self.to_le_bytes());
+    /// Whether the writer is flushed after every send, see [TevClient::set_flush_after_send].
+    pub fn flush_after_send(&self) -> bool {
+        self.flush_after_send
     }
-}
 
-impl TevWritable for f32 {
-    fn write_to(self, writer: &mut TevWriter) {
-        writer.target.extend_from_slice(&self.to_le_bytes());
+    /// Whether a write failed after sending only part of a packet. _tev_ would misinterpret anything sent afterwards,
+    /// so all sends fail with [TevError::Desynchronized] until the connection is replaced by [TevClient::reconnect].
+    /// If reconnecting is enabled, for example by [TevClient::with_reconnect], this happens automatically on the next send.
+    pub fn is_desynchronized(&self) -> bool {
+        self.desynchronized.is_some()
     }
-}
 
-impl TevWritable for &'_ str {
-    fn write_to(self, writer: &mut TevWriter) {
-        assert!(!self.contains('\0'), "cannot send strings containing '\\0'");
-        writer.target.extend_from_slice(self.as_bytes());
-        writer.target.push(0);
+    /// Set the size in bytes of the largest packet this client will send, including the length prefix.
+    /// Sending a larger packet fails with [TevError::PacketTooLarge] before anything is written,
+    /// which catches mistakes like an update with a wrong width before they flood the connection.
+    /// This is `None` by default, which only limits packets to the 4 GiB the protocol allows.
+    ///
+    /// The limit applies to each packet separately, so batches and chunked updates can be larger in total.
+    /// Packets are checked while they're serialized, so an oversized packet doesn't grow the internal buffer either.
+    pub fn set_max_packet_size(&mut self, max: Option<usize>) {
+        self.max_packet_size = max;
     }
-}
 
-impl From<std::io::Error> for TevError {
-    fn from(io: std::io::Error) -> Self {
-        TevError::IO { io }
+    /// The largest packet this client will send, see [TevClient::set_max_packet_size].
+    pub fn max_packet_size(&self) -> Option<usize> {
+        self.max_packet_size
+    }
+
+    /// Flush the underlying writer, so all packets sent so far are passed on to _tev_.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.socket.flush()
+    }
+
+    /// The host _tev_ reported on stdout, exactly as it was printed.
+    /// This is only available if this client was created by spawning _tev_, otherwise it's `None`.
+    ///
+    /// This can be used to open additional connections to the same instance.
+    pub fn reported_host(&self) -> Option<&str> {
+        self.reported_host.as_deref()
+    }
+
+    /// Whether the _tev_ this client spawned started a new instance or connected to an already running primary instance,
+    /// based on what it printed on stdout. This decides whether killing [TevClient::child_mut] closes the viewer.
+    pub fn connection_kind(&self) -> ConnectionKind {
+        self.connection_kind
+    }
+
+    /// The _tev_ process this client spawned, for example to kill it on shutdown or to check whether it exited.
+    /// This is `None` if this client did not spawn _tev_ itself.
+    ///
+    /// If another _tev_ instance was already running the spawned process only forwards to it and then exits,
+    /// so killing it won't close the instance that is actually showing the images, see [TevClient::connection_kind].
+    pub fn child_mut(&mut self) -> Option<&mut Child> {
+        self.child.as_mut()
+    }
+
+    /// Take the rest of the stdout of the _tev_ process this client spawned, starting right after the line that
+    /// reported the address. This can be used to read the diagnostics _tev_ prints, for example when an image fails to load.
+    ///
+    /// Returns `None` if this client did not spawn _tev_ itself or if stdout was already taken.
+    ///
+    /// Until stdout is taken it's kept open but not read, so _tev_ blocks once the pipe buffer is full.
+    /// Take it and read it or drop it if _tev_ may print a lot of output.
+    pub fn take_stdout(&mut self) -> Option<BufReader<ChildStdout>> {
+        self.stdout.take()
+    }
+
+    /// Send a command to _tev_. A command is any struct in this crate that implements [TevPacket].
+    ///
+    /// If any string in the packet contains `'\0'` nothing is sent and an error with kind
+    /// [io::ErrorKind::InvalidInput] is returned, since _tev_ can't receive such strings.
+    ///
+    /// The packet is written with a single `write_all` call, which for a socket means it has been passed on to the OS.
+    /// The writer is only flushed if [TevClient::set_flush_after_send] is enabled.
+    /// # Example
+    /// ```no_run
+    /// # use tev_client::{TevClient, PacketOpenImage};
+    /// # fn main() -> std::io::Result<()> {
+    /// # use tev_client::PacketCloseImage;
+    /// # let mut client: TevClient = unimplemented!();
+    /// client.send(PacketCloseImage { image_name: "test.exf" })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send(&mut self, packet: impl TevPacket) -> io::Result<()> {
+        self.send_counted(packet).map(|_| ())
+    }
+
+    /// The same as [TevClient::send], but borrows the packet so it can be kept around and sent again later.
+    ///
+    /// ```no_run
+    /// # use tev_client::{TevClient, PacketCloseImageOwned};
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut first: TevClient = unimplemented!();
+    /// # let mut second: TevClient = unimplemented!();
+    /// let packet = PacketCloseImageOwned { image_name: "test.exr".to_string() };
+    /// first.send_ref(&packet)?;
+    /// second.send_ref(&packet)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_ref(&mut self, packet: &(impl TevPacket + ?Sized)) -> io::Result<()> {
+        encode_framed(packet, self.max_packet_size, &mut self.buffer)?;
+        self.write_buffer()
+    }
+
+    /// The same as [TevClient::send], but returns the number of bytes that were written,
+    /// including the length prefix of the packet.
+    pub fn send_counted(&mut self, packet: impl TevPacket) -> io::Result<usize> {
+        encode_framed(&packet, self.max_packet_size, &mut self.buffer)?;
+        self.write_buffer()?;
+        Ok(self.buffer.len())
+    }
+
+    /// Write packets that were already serialized, for example by [protocol::encode_packet],
+    /// so static packets only have to be serialized once no matter how often they're sent.
+    ///
+    /// ```no_run
+    /// # use tev_client::{TevClient, PacketCloseImage};
+    /// # use tev_client::protocol::encode_packet;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut clients: Vec<TevClient> = unimplemented!();
+    /// let bytes = encode_packet(&PacketCloseImage { image_name: "background.exr" })?;
+    /// for client in &mut clients {
+    ///     client.send_raw(&bytes)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] without writing anything
+    /// if `bytes` doesn't consist of complete packets according to their length prefixes.
+    /// The contents of the packets are not checked.
+    pub fn send_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        check_framing(bytes)?;
+        self.write_bytes(bytes, &[])
+    }
+
+    /// Send multiple commands to _tev_ using a single write to the socket.
+    /// Each packet is still framed separately, this only reduces the number of system calls and small TCP segments.
+    /// # Example
+    /// ```no_run
+    /// # use tev_client::{TevClient, PacketCloseImage};
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// client.send_batch(&[
+    ///     &PacketCloseImage { image_name: "a.exr" },
+    ///     &PacketCloseImage { image_name: "b.exr" },
+    /// ])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_batch(&mut self, packets: &[&dyn TevPacket]) -> io::Result<()> {
+        self.buffer.clear();
+        for packet in packets {
+            append_framed(*packet, self.max_packet_size, &mut self.buffer)?;
+        }
+        self.write_buffer()
+    }
+
+    /// Send packets of possibly different types one after the other, for example to replay a recorded command stream.
+    /// Unlike [TevClient::send_batch] each packet is written separately,
+    /// and sending stops at the first error which is returned together with the index of the packet that caused it.
+    ///
+    /// ```no_run
+    /// # use tev_client::{TevClient, TevPacket, PacketCloseImageOwned, PacketCreateImageOwned};
+    /// # fn main() {
+    /// # let mut client: TevClient = unimplemented!();
+    /// let recorded: Vec<Box<dyn TevPacket>> = vec![
+    ///     Box::new(PacketCreateImageOwned {
+    ///         image_name: "test".to_string(),
+    ///         grab_focus: false,
+    ///         width: 2,
+    ///         height: 2,
+    ///         channel_names: vec!["L".to_string()],
+    ///     }),
+    ///     Box::new(PacketCloseImageOwned { image_name: "test".to_string() }),
+    /// ];
+    /// if let Err((index, error)) = client.send_dyn(recorded.iter().map(|p| p.as_ref())) {
+    ///     eprintln!("failed to send packet {}: {}", index, error);
+    /// }
+    /// # }
+    /// ```
+    pub fn send_dyn<'p>(&mut self, packets: impl IntoIterator<Item=&'p dyn TevPacket>) -> Result<(), (usize, io::Error)> {
+        for (i, packet) in packets.into_iter().enumerate() {
+            self.send_ref(packet).map_err(|e| (i, e))?;
+        }
+        Ok(())
+    }
+
+    /// Send a [PacketOpenImage] using the packet versions selected by [TevClient::set_protocol_version].
+    /// With [TevProtocol::Legacy] it's sent as a [PacketOpenImageV1] instead.
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if the packet has a channel selector
+    /// and the legacy protocol is used, since [PacketOpenImageV1] doesn't support one.
+    pub fn send_open(&mut self, packet: PacketOpenImage) -> io::Result<()> {
+        self.buffer.clear();
+        self.protocol.append_open(&packet, self.max_packet_size, &mut self.buffer)?;
+        self.write_buffer()
+    }
+
+    /// Send a [PacketUpdateImage] using the packet versions selected by [TevClient::set_protocol_version].
+    /// With [TevProtocol::Legacy] the update is split into one [PacketUpdateImageV1] per channel,
+    /// which are all sent with a single write.
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if the packet is invalid, see [PacketUpdateImage::validate].
+    pub fn send_update<S: AsRef<str>>(&mut self, packet: PacketUpdateImage<S>) -> io::Result<()> {
+        packet.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        match self.protocol {
+            TevProtocol::Current => self.send(packet),
+            TevProtocol::Legacy => {
+                let pixel_count = usize::try_from(packet.width as u64 * packet.height as u64)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, UpdateImageError::Overflow))?;
+                let mut channel_data = Vec::with_capacity(pixel_count);
+
+                self.buffer.clear();
+                for (c, channel_name) in packet.channel_names.iter().enumerate() {
+                    let offset = packet.channel_offsets[c] as usize;
+                    let stride = packet.channel_strides[c] as usize;
+
+                    channel_data.clear();
+                    channel_data.extend((0..pixel_count).map(|i| packet.data[offset + i * stride]));
+
+                    let channel_packet = PacketUpdateImageV1 {
+                        image_name: packet.image_name,
+                        grab_focus: packet.grab_focus,
+                        channel_name: channel_name.as_ref(),
+                        x: packet.x,
+                        y: packet.y,
+                        width: packet.width,
+                        height: packet.height,
+                        data: &channel_data,
+                    };
+                    append_framed(&channel_packet, self.max_packet_size, &mut self.buffer)?;
+                }
+                self.write_buffer()
+            }
+        }
+    }
+
+    /// The same as [TevClient::send_update], but the pixel data is written to the socket straight from `packet.data`
+    /// instead of being copied into the internal buffer first. Only the fields before the data are serialized.
+    ///
+    /// This is useful for very large updates from memory that's already laid out correctly, for example a frame in a
+    /// memory-mapped file. Such a mapping can be viewed as a `&[f32]` without copying using `bytemuck::cast_slice`,
+    /// as long as the file is written with little-endian floats:
+    /// ```ignore
+    /// let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    /// let data: &[f32] = bytemuck::cast_slice(&mmap);
+    /// let packet = PacketUpdateImage::interleaved("frame", &["R", "G", "B"], 0, 0, width, height, data)?;
+    /// client.send_update_direct(packet.as_packet())?;
+    /// ```
+    ///
+    /// On big-endian targets and with [TevProtocol::Legacy] the data has to be converted first,
+    /// so this falls back to [TevClient::send_update].
+    ///
+    /// This is only available with the `bytemuck` feature.
+    #[cfg(feature = "bytemuck")]
+    pub fn send_update_direct<S: AsRef<str>>(&mut self, packet: PacketUpdateImage<S>) -> io::Result<()> {
+        if cfg!(target_endian = "big") || self.protocol == TevProtocol::Legacy {
+            return self.send_update(packet);
+        }
+        packet.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let data: &[u8] = bytemuck::cast_slice(packet.data);
+        self.buffer.clear();
+        append_framed_extra(&UpdateImageHeader(&packet), data.len(), self.max_packet_size, &mut self.buffer)?;
+
+        let buffer = std::mem::take(&mut self.buffer);
+        let result = self.write_bytes(&buffer, data);
+        self.buffer = buffer;
+        result
+    }
+
+    /// The same as [TevClient::send_update], except that an update without any pixels or channels is skipped
+    /// instead of returning [UpdateImageError::NoPixels] or [UpdateImageError::NoChannels].
+    /// This is convenient in generic update loops where an empty region is legitimate.
+    ///
+    /// ```no_run
+    /// # use tev_client::{PacketUpdateImage, TevClient};
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// // the last tile of a row can be empty, this doesn't send anything
+    /// client.send_update_lenient(PacketUpdateImage {
+    ///     image_name: "test",
+    ///     grab_focus: false,
+    ///     channel_names: &["R"],
+    ///     channel_offsets: &[0],
+    ///     channel_strides: &[1],
+    ///     x: 16,
+    ///     y: 0,
+    ///     width: 0,
+    ///     height: 16,
+    ///     data: &[],
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_update_lenient<S: AsRef<str>>(&mut self, packet: PacketUpdateImage<S>) -> io::Result<()> {
+        if packet.width == 0 || packet.height == 0 || packet.channel_names.is_empty() {
+            return Ok(());
+        }
+        self.send_update(packet)
+    }
+
+    /// Send a [PacketUpdateImage] as a sequence of smaller updates of at most `rows_per_chunk` rows each.
+    /// The end result in _tev_ is the same as sending the packet at once,
+    /// but only a single chunk has to be serialized in memory at a time.
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] if the packet is invalid, see [PacketUpdateImage::validate].
+    ///
+    /// # Panics
+    /// Panics if `rows_per_chunk` is zero.
+    pub fn send_update_chunked<S: AsRef<str>>(&mut self, packet: PacketUpdateImage<S>, rows_per_chunk: u32) -> io::Result<()> {
+        assert_ne!(rows_per_chunk, 0, "Must send at least one row per chunk");
+        packet.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let width = packet.width as u64;
+        let mut chunk_offsets = vec![0; packet.channel_offsets.len()];
+
+        let mut start_row = 0;
+        while start_row < packet.height {
+            let rows = rows_per_chunk.min(packet.height - start_row);
+            let start_pixel = start_row as u64 * width;
+            let end_pixel = start_pixel + rows as u64 * width;
+
+            // the range of data used by this chunk, over all channels
+            let channels = packet.channel_offsets.iter().zip(packet.channel_strides);
+            let data_start = channels.clone().map(|(&o, &s)| o + start_pixel * s).min().unwrap();
+            let data_end = channels.map(|(&o, &s)| o + (end_pixel - 1) * s + 1).max().unwrap();
+
+            for (chunk_offset, (&o, &s)) in chunk_offsets.iter_mut().zip(packet.channel_offsets.iter().zip(packet.channel_strides)) {
+                *chunk_offset = o + start_pixel * s - data_start;
+            }
+
+            self.send_update(PacketUpdateImage {
+                image_name: packet.image_name,
+                grab_focus: packet.grab_focus,
+                channel_names: packet.channel_names,
+                channel_offsets: &chunk_offsets,
+                channel_strides: packet.channel_strides,
+                x: packet.x,
+                y: packet.y + start_row,
+                width: packet.width,
+                height: rows,
+                data: &packet.data[data_start as usize..data_end as usize],
+            })?;
+
+            start_row += rows;
+        }
+
+        Ok(())
+    }
+
+    /// Write the already serialized packets in the buffer, reconnecting once if that's enabled.
+    fn write_buffer(&mut self) -> io::Result<()> {
+        let buffer = std::mem::take(&mut self.buffer);
+        let result = self.write_bytes(&buffer, &[]);
+        self.buffer = buffer;
+        result
+    }
+
+    /// Write already serialized packets followed by `trailer`, reconnecting once if that's enabled.
+    /// Reconnecting only happens while writing `bytes`, so a packet is never split across connections
+    /// as long as `bytes` contains its start.
+    fn write_bytes(&mut self, bytes: &[u8], trailer: &[u8]) -> io::Result<()> {
+        // packets serialized by this client are already checked, but raw and multicast bytes are not
+        if let Some(max) = self.max_packet_size {
+            check_packet_sizes(bytes, max)?;
+        }
+        #[cfg(feature = "tracing")]
+        trace_packets(bytes);
+        if let Some(written) = self.desynchronized {
+            match self.reconnect_hook {
+                Some(reconnect) => reconnect(self).map_err(io::Error::other)?,
+                None => return Err(io::Error::other(TevError::Desynchronized { written })),
+            }
+        }
+
+        let mut written = 0;
+        let mut result = write_all_counted(&mut self.socket, bytes, &mut written);
+        if let (Err(e), Some(reconnect)) = (&result, self.reconnect_hook) {
+            if is_broken_connection(e) {
+                let kind = e.kind();
+                result = match reconnect(self) {
+                    Ok(()) => {
+                        written = 0;
+                        write_all_counted(&mut self.socket, bytes, &mut written)
+                    }
+                    Err(tev) => Err(io::Error::new(kind, tev)),
+                };
+            }
+        }
+        if result.is_ok() {
+            result = write_all_counted(&mut self.socket, trailer, &mut written);
+        }
+        if result.is_err() && written != 0 {
+            self.desynchronized = Some(written);
+        }
+
+        let result = match result {
+            Ok(()) if self.flush_after_send => self.socket.flush(),
+            result => result,
+        };
+
+        // a write timeout is reported as WouldBlock on unix and TimedOut on windows
+        result.map_err(|e| match e.kind() {
+            io::ErrorKind::WouldBlock => io::Error::new(io::ErrorKind::TimedOut, "timed out writing to tev"),
+            _ => e,
+        })
     }
 }
 
-impl Display for TevError {
+impl<W: Write + std::fmt::Debug> std::fmt::Debug for TevClient<W> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // the buffer is left out on purpose, it can be very large and its contents are meaningless between sends
+        f.debug_struct("TevClient")
+            .field("socket", &self.socket)
+            .field("reported_host", &self.reported_host)
+            .field("connection_kind", &self.connection_kind)
+            .field("command", &self.command)
+            .field("child", &self.child)
+            .field("default_grab_focus", &self.default_grab_focus)
+            .field("flush_after_send", &self.flush_after_send)
+            .field("max_packet_size", &self.max_packet_size)
+            .field("images", &self.images)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Which of the addresses a host resolves to are tried when connecting, and in which order.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum AddressFamily {
+    /// Try all addresses in the order they were resolved in, this is the default.
+    #[default]
+    Any,
+    /// Only try IPv4 addresses.
+    V4Only,
+    /// Only try IPv6 addresses.
+    V6Only,
+    /// Try all addresses, but the IPv4 ones first.
+    PreferV4,
+    /// Try all addresses, but the IPv6 ones first.
+    PreferV6,
+}
+
+impl AddressFamily {
+    /// Filter and reorder `addrs` according to this preference, otherwise keeping the original order.
+    fn order(self, mut addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
         match self {
-            TevError::Command { io } =>
-                write!(f, "error during command execution: {}", io),
-            TevError::Stdout { io } =>
-                write!(f, "error during stdout reading: {}", io),
-            TevError::NoSocketResponse { read } =>
-                write!(f, "stdout did not contain socket, got '{}'", read),
-            TevError::TcpConnect { host, io } =>
-                write!(f, "error during attempted tcp connection to '{}': {}", host, io),
-            TevError::IO { io } =>
-                write!(f, "generic IO error: {}", io),
+            AddressFamily::Any => {}
+            AddressFamily::V4Only => addrs.retain(SocketAddr::is_ipv4),
+            AddressFamily::V6Only => addrs.retain(SocketAddr::is_ipv6),
+            AddressFamily::PreferV4 => addrs.sort_by_key(SocketAddr::is_ipv6),
+            AddressFamily::PreferV6 => addrs.sort_by_key(SocketAddr::is_ipv4),
         }
+        addrs
     }
 }
 
-impl std::error::Error for TevError {
-    fn source(&self) -> Option<&(dyn Error + 'static)> {
-        match self {
-            TevError::Command { io } | TevError::Stdout { io } |
-            TevError::TcpConnect { host: _, io } | TevError::IO { io } =>
-                Some(io),
-            TevError::NoSocketResponse { read: _ } =>
-                None,
+/// The hostname _tev_ listens on if no other one is given on the command line.
+pub const DEFAULT_HOSTNAME: &str = "127.0.0.1:14158";
+
+/// The timeout used by [TevClient::spawn] when connecting to _tev_.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A _tev_ process started by [spawn_connect].
+struct Spawned {
+    socket: TcpStream,
+    host: String,
+    kind: ConnectionKind,
+    child: Child,
+    /// The stdout of `child`, positioned right after the line containing `host`.
+    stdout: BufReader<ChildStdout>,
+}
+
+/// Spawn _tev_ and connect to the host it reports on stdout.
+fn spawn_connect(
+    command: &mut Command,
+    patterns: &[&str],
+    family: AddressFamily,
+    timeout: Duration,
+    expected_port: Option<u16>,
+) -> Result<Spawned, TevError> {
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!("spawn", pattern = tracing::field::Empty, host = tracing::field::Empty).entered();
+
+    let mut child = command.stdout(Stdio::piped()).spawn()
+        .map_err(|io| TevError::Command { io })?;
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    let mut read = String::new();
+    let mut line = String::new();
+    loop {
+        // read line by line instead of using lines() to keep stdout around, including anything already buffered
+        line.clear();
+        if stdout.read_line(&mut line).map_err(|io| TevError::Stdout { io })? == 0 {
+            break;
+        }
+        let line = line.trim_end_matches(&['\r', '\n'][..]);
+
+        let found = parse_host_with(line, patterns)
+            .filter(|&(_, host)| expected_port.is_none() || host_port(host) == expected_port);
+        if let Some((pattern, host)) = found {
+            #[cfg(feature = "tracing")]
+            {
+                span.record("pattern", pattern);
+                span.record("host", host);
+            }
+            check_host(host)?;
+            let socket = connect_host_timeout(host, family, timeout)?;
+            let kind = ConnectionKind::from_pattern(pattern);
+            return Ok(Spawned { socket, host: host.to_string(), kind, child, stdout });
+        }
+
+        read.push_str(line);
+        read.push('\n');
+    }
+
+    match child.stderr.take() {
+        Some(mut stderr) => {
+            // stdout was closed so tev has most likely exited, and then this doesn't block
+            let mut captured = String::new();
+            stderr.read_to_string(&mut captured).ok();
+            Err(TevError::StartupFailed { read, stderr: captured })
+        }
+        None => Err(TevError::NoSocketResponse { read }),
+    }
+}
+
+/// The port of `host` if it looks like `host:port`.
+fn host_port(host: &str) -> Option<u16> {
+    host.rsplit_once(':')?.1.parse().ok()
+}
+
+/// Check that `host` as reported by _tev_ looks like `host:port` before trying to connect to it.
+fn check_host(host: &str) -> Result<(), TevError> {
+    let valid = host.parse::<SocketAddr>().is_ok() || match host.rsplit_once(':') {
+        Some((name, port)) => !name.is_empty() && !name.contains(char::is_whitespace) && port.parse::<u16>().is_ok(),
+        None => false,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(TevError::InvalidHost { host: host.to_string() })
+    }
+}
+
+/// The same as [Write::write_all], but `written` is increased by the number of bytes written even if it fails.
+fn write_all_counted(writer: &mut impl Write, mut bytes: &[u8], written: &mut usize) -> io::Result<()> {
+    while !bytes.is_empty() {
+        match writer.write(bytes) {
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero, "failed to write whole buffer")),
+            Ok(n) => {
+                *written += n;
+                bytes = &bytes[n..];
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Whether `e` means the other side of the connection is gone.
+fn is_broken_connection(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted | io::ErrorKind::NotConnected
+    )
+}
+
+/// Connect to `host`, trying each address it resolves to in the order given by `family`
+/// and giving up on each of them after `timeout`.
+fn connect_host_timeout(host: &str, family: AddressFamily, timeout: Duration) -> Result<TcpStream, TevError> {
+    let addrs = host.to_socket_addrs().map_err(|io| TevError::TcpConnect { host: host.to_string(), io })?;
+    connect_addrs_timeout(host, family.order(addrs.collect()), timeout)
+}
+
+/// Connect to the first of `addrs` that accepts a connection, `host` is only used for error reporting.
+/// If all of them fail the error of the last attempt is returned.
+fn connect_addrs_timeout(host: &str, addrs: impl IntoIterator<Item=SocketAddr>, timeout: Duration) -> Result<TcpStream, TevError> {
+    let mut last_error = None;
+
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(socket) => return Ok(socket),
+            Err(io) => last_error = Some(io),
+        }
+    }
+
+    Err(match last_error {
+        Some(io) if io.kind() == io::ErrorKind::TimedOut => TevError::Timeout { host: host.to_string() },
+        Some(io) => TevError::TcpConnect { host: host.to_string(), io },
+        None => TevError::TcpConnect {
+            host: host.to_string(),
+            io: io::Error::new(io::ErrorKind::InvalidInput, "host did not resolve to any address"),
+        },
+    })
+}
+
+/// Socket options that are set before connecting, see [TevClient::connect_with].
+///
+/// This is only available with the `socket2` feature.
+#[cfg(feature = "socket2")]
+#[derive(Debug, Clone, Default)]
+pub struct SocketConfig {
+    /// Set `SO_REUSEADDR`, so `local_addr` can be bound again while a previous connection is still in `TIME_WAIT`.
+    pub reuse_address: bool,
+    /// Set `SO_REUSEPORT`, this is only supported on unix and connecting fails elsewhere if it's enabled.
+    pub reuse_port: bool,
+    /// Bind to this local address before connecting, by default the operating system picks an ephemeral port.
+    pub local_addr: Option<SocketAddr>,
+    /// See [TevClient::set_send_buffer_size].
+    pub send_buffer_size: Option<usize>,
+}
+
+/// What kind of _tev_ instance a [TevClient] is connected to, see [TevClient::connection_kind].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ConnectionKind {
+    /// The spawned _tev_ is a new instance that's listening itself, so it's owned by whoever spawned it.
+    NewInstance,
+    /// Another _tev_ instance was already running, the spawned process connected to that primary instance instead.
+    /// Killing the spawned process doesn't close the primary instance.
+    PrimaryInstance,
+    /// The client was not created by spawning _tev_, or _tev_ reported its address using a custom pattern.
+    Unknown,
+}
+
+impl ConnectionKind {
+    /// The kind of connection implied by the pattern that matched on stdout.
+    fn from_pattern(pattern: &str) -> ConnectionKind {
+        match DEFAULT_HOST_PATTERNS.iter().position(|&p| p == pattern) {
+            Some(0) => ConnectionKind::NewInstance,
+            Some(1) => ConnectionKind::PrimaryInstance,
+            _ => ConnectionKind::Unknown,
         }
     }
 }
+
+/// The text _tev_ prints on stdout right before the address it's listening on,
+/// used by [TevClient::spawn] to find the address to connect to.
+pub const DEFAULT_HOST_PATTERNS: &[&str] = &[
+    "Initialized IPC, listening on ",
+    "Connected to primary instance at ",
+];
+
+/// Look for the address following any of `patterns` in a single line of output,
+/// returning the pattern that matched and the address.
+fn parse_host_with<'l, 'p>(line: &'l str, patterns: &[&'p str]) -> Option<(&'p str, &'l str)> {
+    for &pattern in patterns {
+        if let Some(start) = line.find(pattern) {
+            let rest = &line[start + pattern.len()..];
+
+            // cut of any trailing terminal escape codes
+            let end = rest.find('\u{1b}').unwrap_or(rest.len());
+            return Some((pattern, &rest[..end]));
+        }
+    }
+
+    None
+}
+
+/// Serialize a packet including the length prefix _tev_ expects into `buffer`, replacing its previous contents.
+/// Reusing the same buffer across packets avoids reallocating it every time.
+fn encode_framed(packet: &(impl TevPacket + ?Sized), max_len: Option<usize>, buffer: &mut Vec<u8>) -> io::Result<()> {
+    buffer.clear();
+    append_framed(packet, max_len, buffer)
+}
+
+/// Check the length prefix of each packet in `bytes`, which must start with a packet.
+/// The last packet may continue past the end of `bytes`.
+fn check_packet_sizes(bytes: &[u8], max: usize) -> io::Result<()> {
+    let mut start = 0;
+    while let Some(prefix) = bytes.get(start..start + 4) {
+        let len = u32::from_le_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+        if len > max {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, TevError::PacketTooLarge { len, max }));
+        }
+        // a length below 4 would be a bug in the serialization, stop instead of looping forever
+        start += len.max(4);
+    }
+    Ok(())
+}
+
+/// Emit a debug event with the type and length of each packet in `bytes`, which must start with a packet.
+/// The last packet may continue past the end of `bytes`.
+#[cfg(feature = "tracing")]
+fn trace_packets(bytes: &[u8]) {
+    let mut start = 0;
+    while let Some(header) = bytes.get(start..start + 5) {
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        match PacketType::from_u8(header[4]) {
+            Some(packet_type) => tracing::debug!(?packet_type, len, "sending packet"),
+            None => tracing::debug!(packet_type = header[4], len, "sending packet"),
+        }
+        start += len.max(5);
+    }
+}
+
+/// Check that `bytes` consists of complete packets, so writing it can't desynchronize the connection.
+fn check_framing(bytes: &[u8]) -> io::Result<()> {
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let len = match rest.get(..4) {
+            Some(prefix) => u32::from_le_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize,
+            None => 0,
+        };
+        if len < 5 || len > rest.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid packet framing at byte {}", bytes.len() - rest.len()),
+            ));
+        }
+        rest = &rest[len..];
+    }
+    Ok(())
+}
+
+/// Serialize a packet including the length prefix _tev_ expects to the end of `buffer`.
+///
+/// Returns an error with kind [io::ErrorKind::InvalidInput] if the packet contains a string that can't be sent
+/// or is larger than `max_len` or the length prefix allows, in that case `buffer` is left unchanged.
+/// The size is checked while serializing, so `buffer` never grows much past `max_len` for an oversized packet.
+fn append_framed(packet: &(impl TevPacket + ?Sized), max_len: Option<usize>, buffer: &mut Vec<u8>) -> io::Result<()> {
+    append_framed_extra(packet, 0, max_len, buffer)
+}
+
+/// The same as [append_framed], but the length prefix includes `extra_len` bytes
+/// that will be written separately right after the packet.
+fn append_framed_extra(
+    packet: &(impl TevPacket + ?Sized),
+    extra_len: usize,
+    max_len: Option<usize>,
+    buffer: &mut Vec<u8>,
+) -> io::Result<()> {
+    let max = max_len.map_or(u32::MAX as usize, |max| max.min(u32::MAX as usize));
+
+    //reserve space for the packet length
+    let start = buffer.len();
+    buffer.extend_from_slice(&[0, 0, 0, 0]);
+
+    //append the packet
+    let limit = start.saturating_add(max.saturating_sub(extra_len));
+    let mut writer = TevWriter { target: buffer, invalid_string: None, limit, skipped: 0 };
+    writer.write(packet.packet_type());
+    packet.write_to(&mut writer);
+    let skipped = writer.skipped;
+
+    if let Some(string) = writer.invalid_string {
+        buffer.truncate(start);
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot send strings containing '\\0', got {:?}", string),
+        ));
+    }
+
+    //actually fill in the packet length
+    let len = (buffer.len() - start).saturating_add(skipped).saturating_add(extra_len);
+    if len > max {
+        buffer.truncate(start);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, TevError::PacketTooLarge { len, max }));
+    }
+    buffer[start..start + 4].copy_from_slice(&(len as u32).to_le_bytes());
+    Ok(())
+}
+
+/// Opens a new image where `image_name` is the path.
+///
+/// _tev_ doesn't name the opened image after the path alone, see [PacketOpenImage::opened_image_name]
+/// for the name to use in later packets like [PacketCloseImage].
+#[derive(Debug)]
+pub struct PacketOpenImage<'a> {
+    pub image_name: &'a str,
+    pub grab_focus: bool,
+    pub channel_selector: &'a str,
+}
+
+impl PacketOpenImage<'_> {
+    /// The name _tev_ gives the image opened by this packet. This is the path exactly as it was sent
+    /// if the channel selector is empty, and `path:channel_selector` otherwise.
+    /// The path is not normalized, so `./a.exr` and `a.exr` are different images to _tev_.
+    ///
+    /// ```
+    /// # use tev_client::PacketOpenImage;
+    /// let packet = PacketOpenImage { image_name: "render.exr", grab_focus: false, channel_selector: "albedo" };
+    /// assert_eq!(packet.opened_image_name(), "render.exr:albedo");
+    /// ```
+    pub fn opened_image_name(&self) -> String {
+        if self.channel_selector.is_empty() {
+            self.image_name.to_string()
+        } else {
+            format!("{}:{}", self.image_name, self.channel_selector)
+        }
+    }
+}
+
+impl TevPacket for PacketOpenImage<'_> {
+    fn packet_type(&self) -> PacketType {
+        PacketType::OpenImageV2
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        writer.write(self.grab_focus);
+        writer.write(self.image_name);
+        writer.write(self.channel_selector);
+    }
+}
+
+/// Opens a new image where `image_name` is the path, the original version of [PacketOpenImage] used by
+/// [TevProtocol::Legacy]. This version has no channel selector, so all channels of the image are opened.
+#[derive(Debug)]
+pub struct PacketOpenImageV1<'a> {
+    pub image_name: &'a str,
+    pub grab_focus: bool,
+}
+
+impl TevPacket for PacketOpenImageV1<'_> {
+    fn packet_type(&self) -> PacketType {
+        PacketType::OpenImage
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        writer.write(self.grab_focus);
+        writer.write(self.image_name);
+    }
+}
+
+/// Reload an existing image with name or path `image_name` from disk.
+#[derive(Debug)]
+pub struct PacketReloadImage<'a> {
+    pub image_name: &'a str,
+    pub grab_focus: bool,
+}
+
+impl TevPacket for PacketReloadImage<'_> {
+    fn packet_type(&self) -> PacketType {
+        PacketType::ReloadImage
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        writer.write(self.grab_focus);
+        writer.write(self.image_name);
+    }
+}
+
+/// Update part of an existing image with new pixel data.
+///
+/// The pixel for channel `c` at position `(x + i, y + j)` is read from `data[channel_offsets[c] + (j * width + i) * channel_strides[c]]`.
+///
+/// Prefer constructing this packet with [PacketUpdateImage::new], which checks that the fields are consistent.
+/// A packet constructed as a struct literal is only checked when it is sent,
+/// and [TevClient::send] panics if the checks in [PacketUpdateImage::validate] fail.
+#[derive(Debug)]
+pub struct PacketUpdateImage<'a, S: AsRef<str> + 'a> {
+    pub image_name: &'a str,
+    pub grab_focus: bool,
+    pub channel_names: &'a [S],
+    pub channel_offsets: &'a [u64],
+    pub channel_strides: &'a [u64],
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub data: &'a [f32],
+}
+
+/// The reason a [PacketUpdateImage] is invalid, returned by [PacketUpdateImage::new] and [PacketUpdateImage::validate].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum UpdateImageError {
+    /// `channel_names` is empty.
+    NoChannels,
+    /// `channel_names` contains `name` more than once.
+    DuplicateChannel { name: String },
+    /// `channel_names`, `channel_offsets` and `channel_strides` don't all have the same length.
+    ChannelCountMismatch { names: usize, offsets: usize, strides: usize },
+    /// `width` or `height` is zero.
+    NoPixels,
+    /// The length of `data` is not exactly one more than the highest index used by the offsets and strides.
+    DataSizeMismatch { expected: u64, actual: usize },
+    /// The updated region doesn't fit within the image, only checked by [TevImage::update] which knows the image size.
+    OutOfBounds { x: u32, y: u32, width: u32, height: u32, image_width: u32, image_height: u32 },
+    /// The highest index used by the offsets and strides, or the number of values implied by the size of the update,
+    /// doesn't fit in a `u64` or `usize`.
+    Overflow,
+}
+
+impl<'a, S: AsRef<str> + 'a> PacketUpdateImage<'a, S> {
+    /// Construct a new packet, checking that the fields are consistent with [PacketUpdateImage::validate].
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        image_name: &'a str,
+        grab_focus: bool,
+        channel_names: &'a [S],
+        channel_offsets: &'a [u64],
+        channel_strides: &'a [u64],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &'a [f32],
+    ) -> Result<Self, UpdateImageError> {
+        let packet = PacketUpdateImage {
+            image_name,
+            grab_focus,
+            channel_names,
+            channel_offsets,
+            channel_strides,
+            x,
+            y,
+            width,
+            height,
+            data,
+        };
+        packet.validate()?;
+        Ok(packet)
+    }
+
+    /// Check that there is at least one channel and one pixel, that the channel names are unique,
+    /// that the channel slices have matching lengths and that `data` has exactly the length the offsets and strides require.
+    pub fn validate(&self) -> Result<(), UpdateImageError> {
+        validate_update(
+            self.channel_names, self.channel_offsets, self.channel_strides,
+            self.width, self.height, self.data.len(),
+        )
+    }
+}
+
+/// The checks shared by [PacketUpdateImage::validate] and [PacketUpdateImageF16::validate].
+fn validate_update<S: AsRef<str>>(
+    channel_names: &[S],
+    channel_offsets: &[u64],
+    channel_strides: &[u64],
+    width: u32,
+    height: u32,
+    data_len: usize,
+) -> Result<(), UpdateImageError> {
+    let channel_count = channel_names.len();
+    if channel_count == 0 {
+        return Err(UpdateImageError::NoChannels);
+    }
+    if let Some(name) = find_duplicate_channel(channel_names) {
+        return Err(UpdateImageError::DuplicateChannel { name: name.to_string() });
+    }
+    if channel_count != channel_offsets.len() || channel_count != channel_strides.len() {
+        return Err(UpdateImageError::ChannelCountMismatch {
+            names: channel_count,
+            offsets: channel_offsets.len(),
+            strides: channel_strides.len(),
+        });
+    }
+
+    let pixel_count = (width as u64) * (height as u64);
+    if pixel_count == 0 {
+        return Err(UpdateImageError::NoPixels);
+    }
+
+    let max_data_index_used = channel_offsets.iter().zip(channel_strides)
+        .map(|(&o, &s)| (pixel_count - 1).checked_mul(s).and_then(|i| i.checked_add(o)))
+        .collect::<Option<Vec<u64>>>()
+        .and_then(|indices| indices.into_iter().max());
+    let expected = match max_data_index_used.and_then(|i| i.checked_add(1)) {
+        Some(expected) if usize::try_from(expected).is_ok() => expected,
+        _ => return Err(UpdateImageError::Overflow),
+    };
+    if expected != data_len as u64 {
+        return Err(UpdateImageError::DataSizeMismatch { expected, actual: data_len });
+    }
+
+    Ok(())
+}
+
+/// The first channel name that also occurs earlier in `channel_names`, if any.
+fn find_duplicate_channel<S: AsRef<str>>(channel_names: &[S]) -> Option<&str> {
+    channel_names.iter().enumerate()
+        .find(|(i, name)| channel_names[..*i].iter().any(|prev| prev.as_ref() == name.as_ref()))
+        .map(|(_, name)| name.as_ref())
+}
+
+impl<'a, S: AsRef<str> + 'a> TevPacket for PacketUpdateImage<'a, S> {
+    fn packet_type(&self) -> PacketType {
+        PacketType::UpdateImageV3
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        if let Err(e) = self.validate() {
+            panic!("Invalid PacketUpdateImage: {}", e);
+        }
+
+        UpdateImageHeader(self).write_to(writer);
+        writer.write_f32_slice(self.data)
+    }
+}
+
+/// All fields of a [PacketUpdateImage] before the pixel data, used to write the data separately.
+struct UpdateImageHeader<'p, 'a, S: AsRef<str> + 'a>(&'p PacketUpdateImage<'a, S>);
+
+impl<'p, 'a, S: AsRef<str> + 'a> TevPacket for UpdateImageHeader<'p, 'a, S> {
+    fn packet_type(&self) -> PacketType {
+        PacketType::UpdateImageV3
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        let packet = self.0;
+        writer.write(packet.grab_focus);
+        writer.write(packet.image_name);
+        writer.write(packet.channel_names.len() as u32);
+        writer.write_all(packet.channel_names.iter().map(AsRef::as_ref));
+        writer.write(packet.x);
+        writer.write(packet.y);
+        writer.write(packet.width);
+        writer.write(packet.height);
+        writer.write_all(packet.channel_offsets);
+        writer.write_all(packet.channel_strides);
+    }
+}
+
+/// The same as [PacketUpdateImage], except that `data` contains half precision floats.
+/// The values are converted to single precision while the packet is serialized, without allocating a full copy.
+///
+/// `data` holds the raw IEEE 754 binary16 bits of each value. A `&[half::f16]` can be viewed as such a slice without copying
+/// using `HalfFloatSliceExt::reinterpret_cast` from the `half` crate.
+#[derive(Debug)]
+pub struct PacketUpdateImageF16<'a, S: AsRef<str> + 'a> {
+    pub image_name: &'a str,
+    pub grab_focus: bool,
+    pub channel_names: &'a [S],
+    pub channel_offsets: &'a [u64],
+    pub channel_strides: &'a [u64],
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub data: &'a [u16],
+}
+
+impl<'a, S: AsRef<str> + 'a> PacketUpdateImageF16<'a, S> {
+    /// Run the same checks as [PacketUpdateImage::validate].
+    pub fn validate(&self) -> Result<(), UpdateImageError> {
+        validate_update(
+            self.channel_names, self.channel_offsets, self.channel_strides,
+            self.width, self.height, self.data.len(),
+        )
+    }
+}
+
+impl<'a, S: AsRef<str> + 'a> TevPacket for PacketUpdateImageF16<'a, S> {
+    fn packet_type(&self) -> PacketType {
+        PacketType::UpdateImageV3
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        if let Err(e) = self.validate() {
+            panic!("Invalid PacketUpdateImageF16: {}", e);
+        }
+
+        writer.write(self.grab_focus);
+        writer.write(self.image_name);
+        writer.write(self.channel_names.len() as u32);
+        writer.write_all(self.channel_names.iter().map(AsRef::as_ref));
+        writer.write(self.x);
+        writer.write(self.y);
+        writer.write(self.width);
+        writer.write(self.height);
+        writer.write_all(self.channel_offsets);
+        writer.write_all(self.channel_strides);
+
+        writer.reserve(self.data.len() * 4);
+        writer.write_all(self.data.iter().map(|&bits| f16_bits_to_f32(bits)))
+    }
+}
+
+/// The same as [PacketUpdateImage] with interleaved data, see [ChannelLayout::interleaved], except that the values
+/// are produced by an iterator while the packet is serialized. This avoids materializing procedurally generated
+/// content in an intermediate buffer.
+///
+/// `data` is cloned every time the packet is serialized, so it should be cheap to clone, like most lazy iterators are.
+/// Sending this packet panics if `data` doesn't yield exactly `width * height * channel_names.len()` values,
+/// or if that count doesn't fit in a `usize`.
+///
+/// ```no_run
+/// # use tev_client::{TevClient, PacketUpdateImageIter};
+/// # fn main() -> std::io::Result<()> {
+/// # let mut client: TevClient = unimplemented!();
+/// let (width, height) = (256, 256);
+/// client.send(PacketUpdateImageIter {
+///     image_name: "gradient",
+///     grab_focus: false,
+///     channel_names: &["L"],
+///     x: 0,
+///     y: 0,
+///     width,
+///     height,
+///     data: (0..width * height).map(|i| (i % width) as f32 / width as f32),
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PacketUpdateImageIter<'a, S: AsRef<str> + 'a, I: IntoIterator<Item=f32> + Clone> {
+    pub image_name: &'a str,
+    pub grab_focus: bool,
+    pub channel_names: &'a [S],
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub data: I,
+}
+
+impl<'a, S: AsRef<str> + 'a, I: IntoIterator<Item=f32> + Clone> TevPacket for PacketUpdateImageIter<'a, S, I> {
+    fn packet_type(&self) -> PacketType {
+        PacketType::UpdateImageV3
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        let layout = ChannelLayout::interleaved(self.channel_names.len());
+        let expected = layout.data_len(self.width as u64 * self.height as u64);
+        let expected_len = usize::try_from(expected).map_err(|_| UpdateImageError::Overflow).and_then(|len| {
+            validate_update(self.channel_names, layout.offsets(), layout.strides(), self.width, self.height, len)?;
+            Ok(len)
+        });
+        let expected_len = match expected_len {
+            Ok(len) => len,
+            Err(e) => panic!("Invalid PacketUpdateImageIter: {}", e),
+        };
+
+        writer.write(self.grab_focus);
+        writer.write(self.image_name);
+        writer.write(self.channel_names.len() as u32);
+        writer.write_all(self.channel_names.iter().map(AsRef::as_ref));
+        writer.write(self.x);
+        writer.write(self.y);
+        writer.write(self.width);
+        writer.write(self.height);
+        writer.write_all(layout.offsets());
+        writer.write_all(layout.strides());
+
+        // take one value too many to detect long iterators without looping forever on infinite ones
+        let mut actual = 0;
+        for value in self.data.clone().into_iter().take(expected_len.saturating_add(1)) {
+            writer.write(value);
+            actual += 1;
+        }
+        if actual != expected {
+            panic!("Invalid PacketUpdateImageIter: expected {} values but data yielded {}", expected, actual);
+        }
+    }
+}
+
+/// The packet versions used by [TevClient::send_update] and the high-level helpers,
+/// see [TevClient::set_protocol_version].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum TevProtocol {
+    /// The newest packet versions, understood by all recent versions of _tev_.
+    #[default]
+    Current,
+    /// Only the original packet versions, for old _tev_ builds that don't understand [PacketType::UpdateImageV3]
+    /// and [PacketType::OpenImageV2] yet.
+    Legacy,
+}
+
+impl TevProtocol {
+    /// Append `packet` to `buffer`, as a [PacketOpenImageV1] for [TevProtocol::Legacy].
+    /// Fails without appending anything if the framed packet would be longer than `max_len`.
+    fn append_open(self, packet: &PacketOpenImage, max_len: Option<usize>, buffer: &mut Vec<u8>) -> io::Result<()> {
+        match self {
+            TevProtocol::Current => append_framed(packet, max_len, buffer),
+            TevProtocol::Legacy => {
+                if !packet.channel_selector.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("channel selector '{}' can't be sent with the legacy protocol", packet.channel_selector),
+                    ));
+                }
+                append_framed(&PacketOpenImageV1 { image_name: packet.image_name, grab_focus: packet.grab_focus }, max_len, buffer)
+            }
+        }
+    }
+}
+
+/// Update the pixels of a single channel, the original version of [PacketUpdateImage] used by [TevProtocol::Legacy].
+///
+/// `data` contains the values of the channel for the region in row-major order, so it must have `width * height` values.
+/// Sending this packet panics if that's not the case, see [PacketUpdateImageV1::validate].
+#[derive(Debug)]
+pub struct PacketUpdateImageV1<'a> {
+    pub image_name: &'a str,
+    pub grab_focus: bool,
+    pub channel_name: &'a str,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub data: &'a [f32],
+}
+
+impl PacketUpdateImageV1<'_> {
+    /// Check that there is at least one pixel and that `data` contains exactly one value for each of them.
+    pub fn validate(&self) -> Result<(), UpdateImageError> {
+        let pixel_count = self.width as u64 * self.height as u64;
+        if pixel_count == 0 {
+            return Err(UpdateImageError::NoPixels);
+        }
+        if pixel_count != self.data.len() as u64 {
+            return Err(UpdateImageError::DataSizeMismatch { expected: pixel_count, actual: self.data.len() });
+        }
+        Ok(())
+    }
+}
+
+impl TevPacket for PacketUpdateImageV1<'_> {
+    fn packet_type(&self) -> PacketType {
+        PacketType::UpdateImage
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        if let Err(e) = self.validate() {
+            panic!("Invalid PacketUpdateImageV1: {}", e);
+        }
+
+        writer.write(self.grab_focus);
+        writer.write(self.image_name);
+        writer.write(self.channel_name);
+        writer.write(self.x);
+        writer.write(self.y);
+        writer.write(self.width);
+        writer.write(self.height);
+        writer.write_f32_slice(self.data);
+    }
+}
+
+/// Close an image.
+///
+/// `image_name` must match the name of the image in _tev_ exactly. For images opened with [PacketOpenImage]
+/// that's not always the path, see [PacketOpenImage::opened_image_name]. Nothing happens if no image matches.
+#[derive(Debug)]
+pub struct PacketCloseImage<'a> {
+    pub image_name: &'a str,
+}
+
+impl TevPacket for PacketCloseImage<'_> {
+    fn packet_type(&self) -> PacketType {
+        PacketType::CloseImage
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        writer.write(self.image_name);
+    }
+}
+
+/// Create a new image with name `image_name`, size (`width`, `height`) and channels `channel_names`.
+/// The image starts out black, [TevClient::create_and_fill] and [TevClient::show_image] create an image
+/// and fill it with pixel data in one step.
+///
+/// Sending this packet panics if the channel names aren't unique, see [PacketCreateImage::validate].
+#[derive(Debug)]
+pub struct PacketCreateImage<'a, S: AsRef<str> + 'a> {
+    pub image_name: &'a str,
+    pub grab_focus: bool,
+    pub width: u32,
+    pub height: u32,
+    pub channel_names: &'a [S],
+}
+
+impl<'a> PacketCreateImage<'a, &'a str> {
+    /// Start building a [PacketCreateImage] with the given name and size.
+    /// By default `grab_focus` is false and the channels are `R`, `G` and `B`.
+    ///
+    /// ```no_run
+    /// # use tev_client::{TevClient, PacketCreateImage};
+    /// # fn main() -> std::io::Result<()> {
+    /// # let mut client: TevClient = unimplemented!();
+    /// client.send(PacketCreateImage::builder("test", 1920, 1080).build())?;
+    /// client.send(PacketCreateImage::builder("test_alpha", 1920, 1080).channels(&["R", "G", "B", "A"]).build())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder(image_name: &'a str, width: u32, height: u32) -> PacketCreateImageBuilder<'a, &'a str> {
+        PacketCreateImageBuilder {
+            packet: PacketCreateImage {
+                image_name,
+                grab_focus: false,
+                width,
+                height,
+                channel_names: &["R", "G", "B"],
+            }
+        }
+    }
+}
+
+/// The reason a [PacketCreateImage] is invalid, returned by [PacketCreateImage::validate].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CreateImageError {
+    /// `channel_names` contains `name` more than once.
+    DuplicateChannel { name: String },
+}
+
+impl<'a, S: AsRef<str> + 'a> PacketCreateImage<'a, S> {
+    /// Check that the channel names are unique, _tev_ doesn't handle images with duplicate channels correctly.
+    pub fn validate(&self) -> Result<(), CreateImageError> {
+        match find_duplicate_channel(self.channel_names) {
+            Some(name) => Err(CreateImageError::DuplicateChannel { name: name.to_string() }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A builder for [PacketCreateImage], constructed using [PacketCreateImage::builder].
+#[derive(Debug)]
+pub struct PacketCreateImageBuilder<'a, S: AsRef<str> + 'a> {
+    packet: PacketCreateImage<'a, S>,
+}
+
+impl<'a, S: AsRef<str> + 'a> PacketCreateImageBuilder<'a, S> {
+    pub fn grab_focus(mut self, grab_focus: bool) -> Self {
+        self.packet.grab_focus = grab_focus;
+        self
+    }
+
+    pub fn channels<T: AsRef<str> + 'a>(self, channel_names: &'a [T]) -> PacketCreateImageBuilder<'a, T> {
+        let PacketCreateImage { image_name, grab_focus, width, height, channel_names: _ } = self.packet;
+        PacketCreateImageBuilder {
+            packet: PacketCreateImage { image_name, grab_focus, width, height, channel_names }
+        }
+    }
+
+    pub fn build(self) -> PacketCreateImage<'a, S> {
+        self.packet
+    }
+}
+
+impl<'a, S: AsRef<str> + 'a> TevPacket for PacketCreateImage<'a, S> {
+    fn packet_type(&self) -> PacketType {
+        PacketType::CreateImage
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        if let Err(e) = self.validate() {
+            panic!("Invalid PacketCreateImage: {}", e);
+        }
+
+        writer.write(self.grab_focus);
+        writer.write(self.image_name);
+        writer.write(self.width);
+        writer.write(self.height);
+        writer.write(self.channel_names.len() as u32);
+        writer.write_all(self.channel_names.iter().map(AsRef::as_ref));
+    }
+}
+
+/// Draw vector graphics on top of the image `image_name`.
+/// If `append` is false any previously drawn vector graphics are cleared first.
+///
+/// The coordinates are in image pixels, see [VgCommand] for the available commands.
+#[derive(Debug)]
+pub struct PacketVectorGraphics<'a> {
+    pub image_name: &'a str,
+    pub grab_focus: bool,
+    pub append: bool,
+    pub commands: &'a [VgCommand],
+}
+
+impl TevPacket for PacketVectorGraphics<'_> {
+    fn packet_type(&self) -> PacketType {
+        PacketType::VectorGraphics
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        writer.write(self.grab_focus);
+        writer.write(self.image_name);
+        writer.write(self.append);
+        writer.write(self.commands.len() as u32);
+        writer.write_all(self.commands);
+    }
+}
+
+/// A single vector graphics command, these mirror the [NanoVG](https://github.com/memononen/nanovg) drawing api _tev_ uses.
+/// Angles are in radians and colors are in linear space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum VgCommand {
+    Save,
+    Restore,
+    FillColor { r: f32, g: f32, b: f32, a: f32 },
+    Fill,
+    StrokeColor { r: f32, g: f32, b: f32, a: f32 },
+    Stroke,
+    BeginPath,
+    ClosePath,
+    PathWinding { winding: VgWinding },
+    MoveTo { x: f32, y: f32 },
+    LineTo { x: f32, y: f32 },
+    ArcTo { x1: f32, y1: f32, x2: f32, y2: f32, radius: f32 },
+    Arc { cx: f32, cy: f32, radius: f32, angle_begin: f32, angle_end: f32, winding: VgWinding },
+    BezierTo { c1x: f32, c1y: f32, c2x: f32, c2y: f32, x: f32, y: f32 },
+    Circle { cx: f32, cy: f32, radius: f32 },
+    Ellipse { cx: f32, cy: f32, rx: f32, ry: f32 },
+    QuadTo { cx: f32, cy: f32, x: f32, y: f32 },
+    Rect { x: f32, y: f32, width: f32, height: f32 },
+    RoundedRect { x: f32, y: f32, width: f32, height: f32, radius: f32 },
+    RoundedRectVarying {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        radius_top_left: f32,
+        radius_top_right: f32,
+        radius_bottom_right: f32,
+        radius_bottom_left: f32,
+    },
+}
+
+/// The winding direction used by [VgCommand::PathWinding] and [VgCommand::Arc].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum VgWinding {
+    CounterClockwise = 1,
+    Clockwise = 2,
+}
+
+/// The buffer a [TevPacket] writes its fields to, using the encoding described in [protocol].
+pub struct TevWriter<'a> {
+    target: &'a mut Vec<u8>,
+    /// The first string that could not be written, because it contains a `'\0'`.
+    invalid_string: Option<String>,
+    /// The length `target` may grow to, the bytes past it are only counted in `skipped`.
+    limit: usize,
+    /// The number of bytes that were not written because they would have grown `target` past `limit`.
+    skipped: usize,
+}
+
+impl TevWriter<'_> {
+    fn extend(&mut self, bytes: &[u8]) {
+        if self.skipped == 0 && bytes.len() <= self.limit.saturating_sub(self.target.len()) {
+            self.target.extend_from_slice(bytes);
+        } else {
+            self.skipped += bytes.len();
+        }
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.target.reserve(additional.min(self.limit.saturating_sub(self.target.len())));
+    }
+
+    fn write(&mut self, value: impl TevWritable) {
+        value.write_to(self);
+    }
+
+    fn write_all(&mut self, values: impl IntoIterator<Item=impl TevWritable>) {
+        for value in values {
+            value.write_to(self);
+        }
+    }
+
+    /// Write a single byte.
+    pub fn write_u8(&mut self, value: u8) {
+        self.extend(&[value]);
+    }
+
+    /// Write a `bool` as a single byte.
+    pub fn write_bool(&mut self, value: bool) {
+        self.write(value);
+    }
+
+    /// Write a little-endian `u32`.
+    pub fn write_u32(&mut self, value: u32) {
+        self.write(value);
+    }
+
+    /// Write a little-endian `u64`.
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(value);
+    }
+
+    /// Write a little-endian `f32`.
+    pub fn write_f32(&mut self, value: f32) {
+        self.write(value);
+    }
+
+    /// Write a null-terminated string. If `value` contains a `'\0'` itself
+    /// nothing is written and sending the packet fails with [io::ErrorKind::InvalidInput].
+    pub fn write_str(&mut self, value: &str) {
+        self.write(value);
+    }
+
+    /// Write all values one after the other, without a length prefix.
+    pub fn write_f32_slice(&mut self, values: &[f32]) {
+        // the in-memory representation already matches on little-endian targets, so the whole slice can be copied at once
+        #[cfg(all(feature = "bytemuck", target_endian = "little"))]
+        self.extend(bytemuck::cast_slice(values));
+        #[cfg(not(all(feature = "bytemuck", target_endian = "little")))]
+        self.write_all(values);
+    }
+
+    /// Write all values one after the other, without a length prefix.
+    pub fn write_u64_slice(&mut self, values: &[u64]) {
+        self.write_all(values);
+    }
+}
+
+/// The trait implemented by all packets.
+///
+/// It can be implemented outside of this crate too, for example to send packets understood by a modified _tev_.
+/// The length prefix and packet type are written by the client, [TevPacket::write_to] only writes the fields.
+///
+/// ```
+/// # use tev_client::{PacketType, TevClient, TevPacket, TevWriter};
+/// struct CloseAll<'a> {
+///     image_names: &'a [&'a str],
+/// }
+///
+/// impl TevPacket for CloseAll<'_> {
+///     fn packet_type(&self) -> PacketType {
+///         PacketType::CloseImage
+///     }
+///
+///     fn write_to(&self, writer: &mut TevWriter) {
+///         writer.write_u32(self.image_names.len() as u32);
+///         for name in self.image_names {
+///             writer.write_str(name);
+///         }
+///     }
+/// }
+///
+/// let mut client = TevClient::from_writer(Vec::new());
+/// client.send(CloseAll { image_names: &["a.exr", "b.exr"] }).unwrap();
+/// ```
+pub trait TevPacket {
+    /// The type of this packet, written right after the length prefix.
+    fn packet_type(&self) -> PacketType;
+
+    /// Write the fields of this packet, everything after the packet type.
+    fn write_to(&self, writer: &mut TevWriter);
+}
+
+trait TevWritable {
+    fn write_to(self, writer: &mut TevWriter);
+}
+
+impl<T: TevWritable + Copy> TevWritable for &T {
+    fn write_to(self, writer: &mut TevWriter) {
+        (*self).write_to(writer);
+    }
+}
+
+impl TevWritable for bool {
+    fn write_to(self, writer: &mut TevWriter) {
+        writer.extend(&[self as u8]);
+    }
+}
+
+impl TevWritable for PacketType {
+    fn write_to(self, writer: &mut TevWriter) {
+        writer.extend(&[self as u8]);
+    }
+}
+
+impl TevWritable for u32 {
+    fn write_to(self, writer: &mut TevWriter) {
+        writer.extend(&self.to_le_bytes());
+    }
+}
+
+impl TevWritable for u64 {
+    fn write_to(self, writer: &mut TevWriter) {
+        writer.extend(&self.to_le_bytes());
+    }
+}
+
+impl TevWritable for f32 {
+    fn write_to(self, writer: &mut TevWriter) {
+        writer.extend(&self.to_le_bytes());
+    }
+}
+
+impl TevWritable for VgCommand {
+    fn write_to(self, writer: &mut TevWriter) {
+        // each command is a type byte followed by a fixed number of floats
+        let (kind, data): (u8, &[f32]) = match self {
+            VgCommand::Save => (0, &[]),
+            VgCommand::Restore => (1, &[]),
+            VgCommand::FillColor { r, g, b, a } => (2, &[r, g, b, a]),
+            VgCommand::Fill => (3, &[]),
+            VgCommand::StrokeColor { r, g, b, a } => (4, &[r, g, b, a]),
+            VgCommand::Stroke => (5, &[]),
+            VgCommand::BeginPath => (6, &[]),
+            VgCommand::ClosePath => (7, &[]),
+            VgCommand::PathWinding { winding } => (8, &[winding as u8 as f32]),
+            VgCommand::MoveTo { x, y } => (10, &[x, y]),
+            VgCommand::LineTo { x, y } => (11, &[x, y]),
+            VgCommand::ArcTo { x1, y1, x2, y2, radius } => (12, &[x1, y1, x2, y2, radius]),
+            VgCommand::Arc { cx, cy, radius, angle_begin, angle_end, winding } =>
+                (13, &[cx, cy, radius, angle_begin, angle_end, winding as u8 as f32]),
+            VgCommand::BezierTo { c1x, c1y, c2x, c2y, x, y } => (14, &[c1x, c1y, c2x, c2y, x, y]),
+            VgCommand::Circle { cx, cy, radius } => (15, &[cx, cy, radius]),
+            VgCommand::Ellipse { cx, cy, rx, ry } => (16, &[cx, cy, rx, ry]),
+            VgCommand::QuadTo { cx, cy, x, y } => (17, &[cx, cy, x, y]),
+            VgCommand::Rect { x, y, width, height } => (18, &[x, y, width, height]),
+            VgCommand::RoundedRect { x, y, width, height, radius } => (19, &[x, y, width, height, radius]),
+            VgCommand::RoundedRectVarying {
+                x, y, width, height,
+                radius_top_left, radius_top_right, radius_bottom_right, radius_bottom_left,
+            } => (20, &[x, y, width, height, radius_top_left, radius_top_right, radius_bottom_right, radius_bottom_left]),
+        };
+
+        writer.extend(&[kind]);
+        writer.write_all(data);
+    }
+}
+
+impl TevWritable for &'_ str {
+    fn write_to(self, writer: &mut TevWriter) {
+        // tev reads null-terminated strings, so these can't be represented
+        if self.contains('\0') {
+            if writer.invalid_string.is_none() {
+                writer.invalid_string = Some(self.to_string());
+            }
+            return;
+        }
+
+        writer.extend(self.as_bytes());
+        writer.extend(&[0]);
+    }
+}
+
+/// Convert the bits of an IEEE 754 binary16 value to the [f32] with the same value, this conversion is exact.
+fn f16_bits_to_f32(bits: u16) -> f32 {
+    let sign = ((bits >> 15) as u32) << 31;
+    let exponent = ((bits >> 10) & 0x1f) as u32;
+    let mantissa = (bits & 0x3ff) as u32;
+
+    match exponent {
+        // zero and subnormals, these are normal numbers in f32 so let float multiplication normalize them
+        0 => {
+            let magnitude = mantissa as f32 * (1.0 / (1u32 << 24) as f32);
+            f32::from_bits(sign | magnitude.to_bits())
+        }
+        // infinity and NaN
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+        // normal numbers, rebias the exponent from 15 to 127
+        _ => f32::from_bits(sign | ((exponent + 112) << 23) | (mantissa << 13)),
+    }
+}
+
+impl From<std::io::Error> for TevError {
+    fn from(io: std::io::Error) -> Self {
+        TevError::IO { io }
+    }
+}
+
+impl Display for TevError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TevError::Command { io } =>
+                write!(f, "error during command execution: {}", io),
+            TevError::Stdout { io } =>
+                write!(f, "error during stdout reading: {}", io),
+            TevError::NoSocketResponse { read } =>
+                write!(f, "stdout did not contain socket, got '{}'", read.trim_end()),
+            TevError::StartupFailed { read, stderr } =>
+                write!(f, "stdout did not contain socket, got '{}' and stderr '{}'", read.trim_end(), stderr.trim_end()),
+            TevError::TcpConnect { host, io } =>
+                write!(f, "error during attempted tcp connection to '{}': {}", host, io),
+            TevError::Timeout { host } =>
+                write!(f, "timeout during attempted tcp connection to '{}'", host),
+            TevError::InvalidHost { host } =>
+                write!(f, "tev reported an invalid host '{}'", host),
+            TevError::Desynchronized { written } =>
+                write!(f, "connection is desynchronized, a previous write failed after sending {} bytes", written),
+            TevError::PacketTooLarge { len, max } =>
+                write!(f, "packet of {} bytes is too large, the maximum is {} bytes", len, max),
+            TevError::IO { io } =>
+                write!(f, "generic IO error: {}", io),
+        }
+    }
+}
+
+impl std::error::Error for TevError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TevError::Command { io } | TevError::Stdout { io } |
+            TevError::TcpConnect { host: _, io } | TevError::IO { io } =>
+                Some(io),
+            TevError::NoSocketResponse { read: _ } | TevError::StartupFailed { read: _, stderr: _ } |
+            TevError::Timeout { host: _ } | TevError::InvalidHost { host: _ } | TevError::Desynchronized { written: _ } |
+            TevError::PacketTooLarge { len: _, max: _ } =>
+                None,
+        }
+    }
+}
+
+impl Display for UpdateImageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateImageError::NoChannels =>
+                write!(f, "must update at least one channel"),
+            UpdateImageError::DuplicateChannel { name } =>
+                write!(f, "channel '{}' occurs more than once", name),
+            UpdateImageError::ChannelCountMismatch { names, offsets, strides } =>
+                write!(f, "channel count must be consistent, got {} names, {} offsets and {} strides", names, offsets, strides),
+            UpdateImageError::NoPixels =>
+                write!(f, "must update at least one pixel"),
+            UpdateImageError::DataSizeMismatch { expected, actual } =>
+                write!(f, "data size does not match actually used data range, expected {} but got {}", expected, actual),
+            UpdateImageError::OutOfBounds { x, y, width, height, image_width, image_height } =>
+                write!(
+                    f, "region at ({}, {}) with size {}x{} does not fit in image of size {}x{}",
+                    x, y, width, height, image_width, image_height
+                ),
+            UpdateImageError::Overflow =>
+                write!(f, "data range used by the offsets and strides overflows"),
+        }
+    }
+}
+
+impl std::error::Error for UpdateImageError {}
+
+impl Display for CreateImageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CreateImageError::DuplicateChannel { name } =>
+                write!(f, "channel '{}' occurs more than once", name),
+        }
+    }
+}
+
+impl std::error::Error for CreateImageError {}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::time::Duration;
+
+    use crate::{
+        connect_addrs_timeout, AddressFamily, ChannelLayout, ConnectionKind, CreateImageError, DiffMode, ImageData,
+        PacketCloseImage, PacketCloseImageOwned, PacketCreateImage, PacketCreateImageOwned, PacketOpenImage,
+        PacketOpenImageOwned, PacketOpenImageV1, PacketUpdateImage, PacketUpdateImageIter, PacketUpdateImageOwned,
+        PacketVectorGraphics, PacketVectorGraphicsOwned, PreparedUpdate, ScanlineStreamer, TevClient, TevError,
+        TevMulticast, TevPacket, TevProtocol, UpdateImageError, VgCommand, VgWinding,
+    };
+    use crate::test_util::{decode_packets, DecodedPacket, MockTev};
+
+    /// Decode `bytes`, which must contain exactly one update packet.
+    pub(crate) fn single_update(bytes: &[u8]) -> PacketUpdateImageOwned {
+        use std::convert::TryFrom;
+
+        match <[DecodedPacket; 1]>::try_from(decode_packets(bytes).unwrap()) {
+            Ok([DecodedPacket::UpdateImage(update)]) => update,
+            packets => panic!("expected a single update, got {:?}", packets),
+        }
+    }
+
+    /// Decode `bytes`, which must contain a create packet followed by exactly one update packet, and return the update.
+    pub(crate) fn created_update(bytes: &[u8]) -> PacketUpdateImageOwned {
+        use std::convert::TryFrom;
+
+        match <[DecodedPacket; 2]>::try_from(decode_packets(bytes).unwrap()) {
+            Ok([DecodedPacket::CreateImage(_), DecodedPacket::UpdateImage(update)]) => update,
+            packets => panic!("expected a create and an update, got {:?}", packets),
+        }
+    }
+
+    #[test]
+    fn connect_skips_unreachable_addresses() {
+        let unreachable = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap()
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let reachable = listener.local_addr().unwrap();
+
+        let socket = connect_addrs_timeout("test", vec![unreachable, reachable], Duration::from_secs(1)).unwrap();
+        assert_eq!(socket.peer_addr().unwrap(), reachable);
+    }
+
+    #[test]
+    fn connect_reports_last_error() {
+        let unreachable = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap()
+        };
+
+        let result = connect_addrs_timeout("test", vec![unreachable], Duration::from_secs(1));
+        assert!(matches!(result, Err(TevError::TcpConnect { .. })));
+
+        let result = connect_addrs_timeout("test", Vec::<SocketAddr>::new(), Duration::from_secs(1));
+        assert!(matches!(result, Err(TevError::TcpConnect { .. })));
+    }
+
+    #[test]
+    fn is_connected_detects_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TevClient::wrap(TcpStream::connect(listener.local_addr().unwrap()).unwrap());
+        let (server, _) = listener.accept().unwrap();
+        assert!(client.is_connected());
+
+        drop(server);
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!client.is_connected());
+    }
+
+    #[test]
+    fn mock_round_trip() {
+        let mock = MockTev::start().unwrap();
+        let mut client = TevClient::connect_timeout(&mock.host(), Duration::from_secs(1)).unwrap();
+
+        let create = PacketCreateImage::builder("test", 2, 1).build();
+        let update = PacketUpdateImage {
+            image_name: "test",
+            grab_focus: true,
+            channel_names: &["R", "G"],
+            channel_offsets: &[0, 1],
+            channel_strides: &[2, 2],
+            x: 0,
+            y: 0,
+            width: 2,
+            height: 1,
+            data: &[1.0, 2.0, 3.0, 4.0],
+        };
+        let vector_graphics = PacketVectorGraphics {
+            image_name: "test",
+            grab_focus: false,
+            append: true,
+            commands: &[
+                VgCommand::BeginPath,
+                VgCommand::Arc { cx: 1.0, cy: 2.0, radius: 3.0, angle_begin: 0.0, angle_end: 1.0, winding: VgWinding::Clockwise },
+                VgCommand::StrokeColor { r: 1.0, g: 0.0, b: 0.0, a: 1.0 },
+                VgCommand::Stroke,
+            ],
+        };
+
+        let expected = vec![
+            DecodedPacket::CreateImage(PacketCreateImageOwned::from(&create)),
+            DecodedPacket::UpdateImage(PacketUpdateImageOwned::from(&update)),
+            DecodedPacket::VectorGraphics(PacketVectorGraphicsOwned::from(&vector_graphics)),
+        ];
+
+        client.send_batch(&[&create, &update]).unwrap();
+        client.send(vector_graphics).unwrap();
+        drop(client);
+
+        assert_eq!(mock.finish().unwrap(), expected);
+    }
+
+    #[test]
+    fn mock_spawn() {
+        let mock = MockTev::start().unwrap();
+        let mut client = TevClient::spawn(mock.stub_command()).unwrap();
+        assert_eq!(client.reported_host(), Some(mock.host().as_str()));
+        assert_eq!(client.connection_kind(), ConnectionKind::NewInstance);
+        assert!(client.child_mut().unwrap().wait().unwrap().success());
+        drop(client);
+
+        assert_eq!(mock.finish().unwrap(), vec![]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket() {
+        use std::io::Read;
+        use std::os::unix::net::UnixListener;
+
+        let path = std::env::temp_dir().join(format!("tev_client_test_{}.sock", std::process::id()));
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let mut client = TevClient::connect_unix(&path).unwrap();
+        client.send(PacketCloseImage { image_name: "test" }).unwrap();
+        drop(client);
+
+        let mut bytes = Vec::new();
+        listener.accept().unwrap().0.read_to_end(&mut bytes).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(decode_packets(&bytes).unwrap(), vec![
+            DecodedPacket::CloseImage(PacketCloseImageOwned { image_name: "test".to_string() }),
+        ]);
+    }
+
+    #[test]
+    fn address_family_order() {
+        let v4: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let v6: SocketAddr = "[::1]:2".parse().unwrap();
+        let addrs = vec![v6, v4];
+
+        assert_eq!(AddressFamily::Any.order(addrs.clone()), vec![v6, v4]);
+        assert_eq!(AddressFamily::V4Only.order(addrs.clone()), vec![v4]);
+        assert_eq!(AddressFamily::V6Only.order(addrs.clone()), vec![v6]);
+        assert_eq!(AddressFamily::PreferV4.order(addrs.clone()), vec![v4, v6]);
+        assert_eq!(AddressFamily::PreferV6.order(addrs), vec![v6, v4]);
+    }
+
+    #[test]
+    fn check_host_rejects_garbage() {
+        for host in ["127.0.0.1:14158", "[::1]:14158", "localhost:14158"] {
+            assert!(super::check_host(host).is_ok(), "{}", host);
+        }
+        for host in ["", "127.0.0.1", ":14158", "localhost:port", "some text:1"] {
+            assert!(matches!(super::check_host(host), Err(TevError::InvalidHost { .. })), "{}", host);
+        }
+    }
+
+    #[test]
+    fn connect_retry_gives_up() {
+        // find a local port nothing is listening on
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        let result = TevClient::connect_retry(&addr.to_string(), 3, Duration::from_millis(10));
+        assert!(matches!(result, Err(TevError::TcpConnect { .. })));
+    }
+
+    #[test]
+    fn connect_retry_succeeds() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = TevClient::connect_retry(&addr.to_string(), 3, Duration::from_millis(10)).unwrap();
+        assert_eq!(client.peer_addr().unwrap(), addr);
+    }
+
+    #[test]
+    fn multicast_sends_same_bytes() {
+        let clients = vec![TevClient::from_writer(Vec::new()), TevClient::from_writer(Vec::new())];
+        let mut multicast = TevMulticast::new(clients);
+
+        let errors = multicast.send(&PacketCloseImage { image_name: "test.exr" }).unwrap();
+        assert!(errors.is_empty());
+
+        let mut single = TevClient::from_writer(Vec::new());
+        single.send(PacketCloseImage { image_name: "test.exr" }).unwrap();
+
+        for client in multicast.into_clients() {
+            assert_eq!(client.into_inner(), *single.get_ref());
+        }
+    }
+
+    #[test]
+    fn duplicate_channels_rejected() {
+        let update = PacketUpdateImage::interleaved("test", &["R", "G", "R"], 0, 0, 1, 1, &[0.0; 3]);
+        assert_eq!(update.unwrap_err(), UpdateImageError::DuplicateChannel { name: "R".to_string() });
+
+        let create = PacketCreateImage::builder("test", 1, 1).channels(&["R", "R", "B"]).build();
+        assert_eq!(create.validate(), Err(CreateImageError::DuplicateChannel { name: "R".to_string() }));
+
+        let mut client = TevClient::from_writer(Vec::new());
+        let error = client.create_image("test", 1, 1, &["R", "R", "B"]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(client.get_ref().is_empty());
+    }
+
+    #[test]
+    fn write_timeout_reports_timed_out() {
+        // accept the connection but never read from it, so the socket buffers fill up
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        let mut client = TevClient::connect_timeout(&host, Duration::from_secs(1)).unwrap();
+        let _socket = listener.accept().unwrap();
+
+        client.set_write_timeout(Some(Duration::from_millis(50))).unwrap();
+        let data = vec![0.0; 4 * 1024 * 1024];
+        let packet = PacketUpdateImage::interleaved("test", &["L"], 0, 0, 1024, 4 * 1024, &data).unwrap();
+        let error = client.send(packet).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn take_stdout_after_host() {
+        use std::io::Read;
+
+        let mock = MockTev::start().unwrap();
+        let mut command = std::process::Command::new("sh");
+        command.arg("-c").arg(format!("echo '{}'; echo 'after the host'", mock.startup_line()));
+
+        let mut client = TevClient::spawn(command).unwrap();
+        let mut rest = String::new();
+        client.take_stdout().unwrap().read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "after the host\n");
+        assert!(client.take_stdout().is_none());
+    }
+
+    #[test]
+    fn update_out_of_bounds() {
+        let mut client = TevClient::from_writer(Vec::new());
+        let image = client.create_image("test", 4, 4, &["L"]).unwrap();
+        let sent = client.get_ref().len();
+
+        let error = image.update(&mut client, 2, 3, 2, 2, &[0.0; 4]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(client.get_ref().len(), sent);
+
+        image.update(&mut client, 2, 2, 2, 2, &[0.0; 4]).unwrap();
+    }
+
+    #[test]
+    fn show_image_channel_order() {
+        let mut client = TevClient::from_writer(Vec::new());
+        client.show_image("test", 1, 1, &["B", "G", "R", "A"], ImageData::F32(&[0.25, 0.5, 0.75, 1.0])).unwrap();
+
+        let update = created_update(client.get_ref());
+        assert_eq!(update.channel_names, ["B", "G", "R", "A"]);
+        let red = update.channel_offsets[2] as usize;
+        assert_eq!(update.data[red], 0.75);
+    }
+
+    #[test]
+    fn legacy_update_per_channel() {
+        let mut client = TevClient::from_writer(Vec::new());
+        client.set_protocol_version(TevProtocol::Legacy);
+        client.update_pixel("test", 1, 2, &["R", "G"], &[0.25, 0.5]).unwrap();
+
+        let packets = decode_packets(client.get_ref()).unwrap();
+        let payloads: Vec<_> = packets.iter().map(|packet| match packet {
+            DecodedPacket::Unknown { kind: 3, payload } => payload.clone(),
+            _ => panic!("expected a V1 update, got {:?}", packet),
+        }).collect();
+
+        let mut expected = vec![0];
+        expected.extend_from_slice(b"test\0R\0");
+        for value in [1u32, 2, 1, 1] {
+            expected.extend_from_slice(&value.to_le_bytes());
+        }
+        expected.extend_from_slice(&0.25f32.to_le_bytes());
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(payloads[0], expected);
+        assert!(payloads[1].ends_with(&0.5f32.to_le_bytes()));
+    }
+
+    #[test]
+    fn iter_update_matches_slice() {
+        let data: Vec<f32> = (0..12).map(|i| i as f32).collect();
+
+        let mut client = TevClient::from_writer(Vec::new());
+        client.send(PacketUpdateImage::interleaved("test", &["R", "G", "B"], 1, 2, 2, 2, &data).unwrap()).unwrap();
+        client.send(PacketUpdateImageIter {
+            image_name: "test",
+            grab_focus: false,
+            channel_names: &["R", "G", "B"],
+            x: 1,
+            y: 2,
+            width: 2,
+            height: 2,
+            data: (0..12).map(|i| i as f32),
+        }).unwrap();
+
+        let packets = decode_packets(client.get_ref()).unwrap();
+        assert_eq!(packets[0], packets[1]);
+    }
+
+    #[cfg(feature = "socket2")]
+    #[test]
+    fn send_buffer_size() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TevClient::wrap(TcpStream::connect(listener.local_addr().unwrap()).unwrap());
+
+        client.set_send_buffer_size(1 << 16).unwrap();
+        assert!(client.send_buffer_size().unwrap() >= 1 << 16);
+    }
+
+    #[test]
+    fn wrap_checked_rejects_other_servers() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let silent = TcpStream::connect(addr).unwrap();
+        let _silent_peer = listener.accept().unwrap();
+        assert!(TevClient::wrap_checked(silent).is_ok());
+
+        let talking = TcpStream::connect(addr).unwrap();
+        let (mut talking_peer, _) = listener.accept().unwrap();
+        talking_peer.write_all(b"SSH-2.0-OpenSSH\r\n").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(matches!(TevClient::wrap_checked(talking), Err(TevError::TcpConnect { .. })));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn_capture_stderr_on_failure() {
+        let mut command = std::process::Command::new("sh");
+        command.arg("-c").arg("echo 'starting'; echo 'could not bind' >&2");
+
+        match TevClient::spawn_capture_stderr(command) {
+            Err(TevError::StartupFailed { read, stderr }) => {
+                assert_eq!(read, "starting\n");
+                assert_eq!(stderr, "could not bind\n");
+            }
+            other => panic!("expected StartupFailed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn flush_after_send() {
+        let packet = PacketCloseImage { image_name: "test.exr" };
+
+        let mut client = TevClient::from_writer(std::io::BufWriter::new(Vec::new()));
+        client.send_ref(&packet).unwrap();
+        assert!(client.get_ref().get_ref().is_empty());
+        client.flush().unwrap();
+        assert_eq!(client.get_ref().get_ref().len(), 14);
+
+        client.set_flush_after_send(true);
+        client.send_ref(&packet).unwrap();
+        assert_eq!(client.get_ref().get_ref().len(), 28);
+    }
+
+    #[test]
+    fn image_registry() {
+        let mut client = TevClient::from_writer(Vec::new());
+        let image = client.create_image("a", 1, 1, &["L"]).unwrap();
+        client.open_images(&[("b.exr", "", false), ("a", "", false)]).unwrap();
+        client.send(PacketCloseImage { image_name: "b.exr" }).unwrap();
+        assert_eq!(client.image_names().collect::<Vec<_>>(), ["a", "b.exr"]);
+
+        image.close(&mut client).unwrap();
+        assert!(client.close_images(&["b.exr"]).is_empty());
+        assert_eq!(client.image_count(), 0);
+    }
+
+    #[test]
+    fn rename_image() {
+        let mut client = TevClient::from_writer(Vec::new());
+        client.create_image("a", 2, 1, &["R", "G"]).unwrap();
+        client.open_images(&[("b.exr", "R", false)]).unwrap();
+        let start = client.get_ref().len();
+
+        client.rename_image("a", "c").unwrap();
+        client.rename_image("b.exr:R", "d.exr").unwrap();
+        assert_eq!(client.image_names().collect::<Vec<_>>(), ["c", "d.exr:R"]);
+        assert_eq!(client.rename_image("a", "e").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+        let packets = decode_packets(&client.get_ref()[start..]).unwrap();
+        let expected = [
+            DecodedPacket::CreateImage(PacketCreateImageOwned {
+                image_name: "c".to_string(),
+                grab_focus: false,
+                width: 2,
+                height: 1,
+                channel_names: vec!["R".to_string(), "G".to_string()],
+            }),
+            DecodedPacket::CloseImage(PacketCloseImageOwned { image_name: "a".to_string() }),
+            DecodedPacket::OpenImage(PacketOpenImageOwned {
+                image_name: "d.exr".to_string(),
+                grab_focus: false,
+                channel_selector: "R".to_string(),
+            }),
+            DecodedPacket::CloseImage(PacketCloseImageOwned { image_name: "b.exr:R".to_string() }),
+        ];
+        assert_eq!(packets, expected);
+    }
+
+    #[test]
+    fn open_images_focus() {
+        let mut client = TevClient::from_writer(Vec::new());
+        client.open_images(&[("a.exr", "", false), ("b.exr", "R", true), ("c.exr", "", false)]).unwrap();
+
+        let packets = decode_packets(client.get_ref()).unwrap();
+        let focus: Vec<bool> = packets.iter().map(|p| match p {
+            DecodedPacket::OpenImage(p) => p.grab_focus,
+            _ => panic!("unexpected packet {:?}", p),
+        }).collect();
+        assert_eq!(focus, [false, true, false]);
+    }
+
+    #[test]
+    fn close_by_path() {
+        let mut client = TevClient::from_writer(Vec::new());
+        client.open_images(&[("a.exr", "R", false), ("a.exr", "", false), ("b.exr", "", false)]).unwrap();
+        assert_eq!(client.image_names().collect::<Vec<_>>(), ["a.exr:R", "a.exr", "b.exr"]);
+        let start = client.get_ref().len();
+
+        client.close_by_path("a.exr").unwrap();
+        assert_eq!(client.image_names().collect::<Vec<_>>(), ["b.exr"]);
+        assert_eq!(client.close_by_path("a.exr").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+        let packets = decode_packets(&client.get_ref()[start..]).unwrap();
+        let expected = [
+            DecodedPacket::CloseImage(PacketCloseImageOwned { image_name: "a.exr:R".to_string() }),
+            DecodedPacket::CloseImage(PacketCloseImageOwned { image_name: "a.exr".to_string() }),
+        ];
+        assert_eq!(packets, expected);
+    }
+
+    #[test]
+    fn close_all_tracked() {
+        let mut client = TevClient::from_writer(Vec::new());
+        client.create_image("a", 1, 1, &["R"]).unwrap();
+        client.open_images(&[("b.exr", "G", false)]).unwrap();
+        let start = client.get_ref().len();
+
+        assert!(client.close_all_tracked().is_empty());
+        assert_eq!(client.image_count(), 0);
+
+        let packets = decode_packets(&client.get_ref()[start..]).unwrap();
+        let expected = [
+            DecodedPacket::CloseImage(PacketCloseImageOwned { image_name: "a".to_string() }),
+            DecodedPacket::CloseImage(PacketCloseImageOwned { image_name: "b.exr:G".to_string() }),
+        ];
+        assert_eq!(packets, expected);
+    }
+
+    #[test]
+    fn close_all_tracked_keeps_failed() {
+        use crate::protocol::encode_packet;
+
+        let mut client = TevClient::from_writer(FailingWriter { remaining: usize::MAX });
+        for name in ["a", "b", "c"] {
+            client.create_image(name, 1, 1, &["R"]).unwrap();
+        }
+
+        // only leave room for the close packet of "a"
+        client.get_mut().remaining = encode_packet(&PacketCloseImage { image_name: "a" }).unwrap().len();
+        let errors = client.close_all_tracked();
+        let failed: Vec<&str> = errors.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(failed, ["b", "c"]);
+        assert_eq!(client.image_names().collect::<Vec<_>>(), ["b", "c"]);
+    }
+
+    #[test]
+    fn open_image_v1_layout() {
+        let mut client = TevClient::from_writer(Vec::new());
+        client.send(PacketOpenImageV1 { image_name: "test.exr", grab_focus: true }).unwrap();
+        assert_eq!(client.get_ref(), b"\x0f\0\0\0\0\x01test.exr\0");
+
+        let mut legacy = TevClient::from_writer(Vec::new());
+        legacy.set_protocol_version(TevProtocol::Legacy);
+        legacy.open_images(&[("test.exr", "", true)]).unwrap();
+        assert_eq!(legacy.get_ref(), client.get_ref());
+
+        let error = legacy.open_images(&[("test.exr", "R", false)]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn update_u8_normalizes() {
+        let data = [0, 51, 255, 102];
+        let expected: Vec<f32> = data.iter().map(|&v| v as f32 / 255.0).collect();
+
+        for protocol in [TevProtocol::Current, TevProtocol::Legacy] {
+            let mut client = TevClient::from_writer(Vec::new());
+            client.set_protocol_version(protocol);
+            client.update_u8("test", 0, 0, 2, 1, &["R", "G"], &data).unwrap();
+            let error = client.update_u8("test", 0, 0, 2, 1, &["R", "G"], &data[..3]).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+            let mut reference = TevClient::from_writer(Vec::new());
+            reference.set_protocol_version(protocol);
+            reference.send_update(PacketUpdateImage::interleaved("test", &["R", "G"], 0, 0, 2, 1, &expected).unwrap().as_packet()).unwrap();
+            assert_eq!(client.get_ref(), reference.get_ref());
+        }
+    }
+
+    #[test]
+    fn create_and_fill() {
+        let mut client = TevClient::from_writer(Vec::new());
+        let data = [0.0, 0.5, 1.0, 0.25];
+        let image = client.create_and_fill("test", 2, 1, &["R", "G"], &data).unwrap();
+        assert_eq!(image.channel_names(), ["R", "G"]);
+
+        let error = client.create_and_fill("other", 2, 1, &["R", "G"], &data[..3]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        let expected = [
+            DecodedPacket::CreateImage(PacketCreateImageOwned {
+                image_name: "test".to_string(),
+                grab_focus: false,
+                width: 2,
+                height: 1,
+                channel_names: vec!["R".to_string(), "G".to_string()],
+            }),
+            DecodedPacket::UpdateImage(PacketUpdateImageOwned::from(
+                &PacketUpdateImage::interleaved("test", &["R", "G"], 0, 0, 2, 1, &data).unwrap().as_packet(),
+            )),
+        ];
+        assert_eq!(decode_packets(client.get_ref()).unwrap(), expected);
+    }
+
+    #[test]
+    fn update_u8_parallel_normalizes_in_order() {
+        // large enough to be split over multiple threads
+        let (width, height) = (1024, 1024);
+        let data: Vec<u8> = (0..width * height * 3).map(|i| (i % 251) as u8).collect();
+        let expected: Vec<f32> = data.iter().map(|&v| v as f32 / 255.0).collect();
+
+        let mut client = TevClient::from_writer(Vec::new());
+        client.update_u8_parallel("test", 0, 0, width, height, &["R", "G", "B"], &data).unwrap();
+        let error = client.update_u8_parallel("test", 0, 0, width, height, &["R", "G"], &data).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        let mut reference = TevClient::from_writer(Vec::new());
+        let packet = PacketUpdateImage::interleaved("test", &["R", "G", "B"], 0, 0, width, height, &expected).unwrap();
+        reference.send_update(packet.as_packet()).unwrap();
+        assert!(client.get_ref() == reference.get_ref());
+    }
+
+    #[test]
+    fn builder_connect() {
+        let mock = MockTev::start().unwrap();
+        let mut client = TevClient::builder()
+            .hostname(&mock.host())
+            .default_grab_focus(true)
+            .nodelay(false)
+            .connect()
+            .unwrap();
+        assert!(client.default_grab_focus());
+        assert!(!client.get_ref().nodelay().unwrap());
+
+        client.send(PacketCloseImage { image_name: "test" }).unwrap();
+        drop(client);
+        assert_eq!(mock.finish().unwrap().len(), 1);
+
+        let command = TevClient::builder().program("path/to/tev").hostname("127.0.0.1:14159").command();
+        assert_eq!(command.get_program(), "path/to/tev");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["--hostname=127.0.0.1:14159"]);
+    }
+
+    #[test]
+    fn update_channels_from_interleaved() {
+        let rgba: Vec<f32> = (0..2 * 2 * 4).map(|i| i as f32).collect();
+
+        let mut client = TevClient::from_writer(Vec::new());
+        client.update_channels("test", 0, 0, 2, 2, &["B", "A"], &rgba, 4, 2).unwrap();
+        assert!(client.update_channels("test", 0, 0, 2, 2, &["A"], &rgba, 4, 4).is_err());
+        assert!(client.update_channels("test", 0, 0, 2, 2, &["A"], &rgba[1..], 4, 3).is_err());
+        let error = client.update_channels("test", 0, 0, 2, 2, &["A"], &rgba, 4, usize::MAX).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        let update = single_update(client.get_ref());
+        let packet = update.as_packet();
+        let values: Vec<f32> = (0..4).map(|i| packet.data[(packet.channel_offsets[1] + i * packet.channel_strides[1]) as usize]).collect();
+        assert_eq!(values, [3.0, 7.0, 11.0, 15.0]);
+    }
+
+    #[cfg(feature = "socket2")]
+    #[test]
+    fn connect_with_config() {
+        use crate::SocketConfig;
+
+        let mock = MockTev::start().unwrap();
+        let config = SocketConfig {
+            reuse_address: true,
+            reuse_port: cfg!(unix),
+            local_addr: Some("127.0.0.1:0".parse().unwrap()),
+            send_buffer_size: None,
+        };
+        let mut client = TevClient::connect_with(mock.addr(), &config).unwrap();
+        client.send(PacketCloseImage { image_name: "test" }).unwrap();
+        drop(client);
+        assert_eq!(mock.finish().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn send_dyn_reports_index() {
+        let packets: Vec<Box<dyn TevPacket>> = vec![
+            Box::new(PacketCloseImageOwned { image_name: "a".to_string() }),
+            Box::new(PacketOpenImageV1 { image_name: "b", grab_focus: false }),
+            Box::new(PacketCloseImage { image_name: "c\0" }),
+            Box::new(PacketCloseImage { image_name: "d" }),
+        ];
+
+        let mut client = TevClient::from_writer(Vec::new());
+        let (index, error) = client.send_dyn(packets.iter().map(|p| p.as_ref())).unwrap_err();
+        assert_eq!(index, 2);
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(decode_packets(client.get_ref()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn little_endian_layout() {
+        // values with distinct bytes, so a platform-dependent byte order would show up
+        let mut client = TevClient::from_writer(Vec::new());
+        client.send(PacketCreateImage {
+            image_name: "ab",
+            grab_focus: true,
+            width: 0x01020304,
+            height: 2,
+            channel_names: &["R"],
+        }).unwrap();
+        assert_eq!(client.get_ref(), &[
+            0x17, 0, 0, 0,
+            4,
+            1,
+            b'a', b'b', 0,
+            0x04, 0x03, 0x02, 0x01,
+            2, 0, 0, 0,
+            1, 0, 0, 0,
+            b'R', 0,
+        ]);
+
+        let mut client = TevClient::from_writer(Vec::new());
+        client.send(PacketUpdateImage {
+            image_name: "ab",
+            grab_focus: false,
+            channel_names: &["R"],
+            channel_offsets: &[0],
+            channel_strides: &[1],
+            x: 0x0a0b0c0d,
+            y: 2,
+            width: 1,
+            height: 1,
+            data: &[1.5],
+        }).unwrap();
+        assert_eq!(client.get_ref(), &[
+            0x33, 0, 0, 0,
+            6,
+            0,
+            b'a', b'b', 0,
+            1, 0, 0, 0,
+            b'R', 0,
+            0x0d, 0x0c, 0x0b, 0x0a,
+            2, 0, 0, 0,
+            1, 0, 0, 0,
+            1, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+            1, 0, 0, 0, 0, 0, 0, 0,
+            0x00, 0x00, 0xc0, 0x3f,
+        ]);
+    }
+
+    #[test]
+    fn cancel_streamer_clears_remaining() {
+        let mut client = TevClient::from_writer(Vec::new());
+        let mut streamer = ScanlineStreamer::new(&mut client, "test", 2, 3, &["L"]).unwrap();
+        streamer.push_row(&[1.0, 1.0]).unwrap();
+        assert_eq!(streamer.cancel(true).unwrap(), 1);
+
+        let packets = decode_packets(client.get_ref()).unwrap();
+        match &packets[..] {
+            [DecodedPacket::CreateImage(_), DecodedPacket::UpdateImage(_), DecodedPacket::UpdateImage(clear)] => {
+                assert_eq!((clear.y, clear.height), (1, 2));
+                assert_eq!(clear.data, [0.0; 4]);
+            }
+            _ => panic!("expected a create and two updates, got {:?}", packets),
+        }
+    }
+
+    #[test]
+    fn focus_image_changes_nothing() {
+        let mut client = TevClient::from_writer(Vec::new());
+        client.focus_image("test").unwrap();
+
+        let packets = decode_packets(client.get_ref()).unwrap();
+        assert_eq!(packets, [DecodedPacket::VectorGraphics(PacketVectorGraphicsOwned {
+            image_name: "test".to_string(),
+            grab_focus: true,
+            append: true,
+            commands: vec![],
+        })]);
+
+        let mut legacy = TevClient::from_writer(Vec::new());
+        legacy.set_protocol_version(TevProtocol::Legacy);
+        legacy.focus_image("test.exr").unwrap();
+        assert_eq!(legacy.get_ref(), b"\x0f\0\0\0\0\x01test.exr\0");
+    }
+
+    #[test]
+    fn update_overflow_detected() {
+        let validate = |offset, stride, width, height| {
+            PacketUpdateImage::new("test", false, &["R"], &[offset], &[stride], 0, 0, width, height, &[0.0]).unwrap_err()
+        };
+        assert_eq!(validate(0, u64::MAX, 2, 1), UpdateImageError::Overflow);
+        assert_eq!(validate(u64::MAX, 1, 1, 1), UpdateImageError::Overflow);
+        assert_eq!(validate(1, u64::MAX / 4, u32::MAX, u32::MAX), UpdateImageError::Overflow);
+
+        let names = ["R", "G"];
+        assert_eq!(ChannelLayout::planar(2, u64::MAX).data_len(u64::MAX), u64::MAX);
+        assert_eq!(PreparedUpdate::new("test", &names, ChannelLayout::planar(2, u64::MAX), 0, 0, u32::MAX, u32::MAX).unwrap_err(), UpdateImageError::Overflow);
+
+        let mut client = TevClient::from_writer(Vec::new());
+        let error = client.update_channels("test", 0, 0, u32::MAX, u32::MAX, &["A"], &[], usize::MAX, 0).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn direct_update_matches_buffered() {
+        let data: Vec<f32> = (0..2 * 3 * 2).map(|i| i as f32 / 4.0).collect();
+        let packet = PacketUpdateImage::interleaved("test", &["R", "G"], 1, 2, 2, 3, &data).unwrap();
+
+        let mut direct = TevClient::from_writer(Vec::new());
+        direct.send_update_direct(packet.as_packet()).unwrap();
+        let mut buffered = TevClient::from_writer(Vec::new());
+        buffered.send_update(packet.as_packet()).unwrap();
+        assert_eq!(direct.get_ref(), buffered.get_ref());
+    }
+
+    #[test]
+    fn lenient_update_skips_empty() {
+        let mut client = TevClient::from_writer(Vec::new());
+        let empty = PacketUpdateImage::new("test", false, &["R"], &[0], &[1], 0, 0, 0, 4, &[]);
+        assert_eq!(empty.unwrap_err(), UpdateImageError::NoPixels);
+
+        let packet = PacketUpdateImage {
+            image_name: "test",
+            grab_focus: false,
+            channel_names: &["R"],
+            channel_offsets: &[0],
+            channel_strides: &[1],
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 4,
+            data: &[],
+        };
+        client.send_update_lenient(PacketUpdateImage { ..packet }).unwrap();
+        assert!(client.get_ref().is_empty());
+        assert!(client.send_update(packet).is_err());
+    }
+
+    #[test]
+    fn create_layered_names() {
+        let mut client = TevClient::from_writer(Vec::new());
+        let image = client.create_layered("test", 1, 1, &[("", &["R"]), ("specular", &["R", "G"])]).unwrap();
+        assert_eq!(image.channel_names(), ["R", "specular.R", "specular.G"]);
+        assert!(client.create_layered("test", 1, 1, &[("a", &["R"]), ("a", &["R"])]).is_err());
+    }
+
+    /// A writer that accepts `remaining` bytes and fails afterwards.
+    struct FailingWriter {
+        remaining: usize,
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            match buf.len().min(self.remaining) {
+                0 => Err(std::io::Error::other("failed")),
+                n => {
+                    self.remaining -= n;
+                    Ok(n)
+                }
+            }
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn partial_write_desynchronizes() {
+        let packet = PacketCloseImage { image_name: "test" };
+
+        let mut client = TevClient::from_writer(FailingWriter { remaining: 0 });
+        assert!(client.send_ref(&packet).is_err());
+        assert!(!client.is_desynchronized());
+
+        let mut client = TevClient::from_writer(FailingWriter { remaining: 5 });
+        assert!(client.send_ref(&packet).is_err());
+        assert!(client.is_desynchronized());
+
+        client.get_mut().remaining = 100;
+        let error = client.send_ref(&packet).unwrap_err();
+        let inner = error.get_ref().and_then(|e| e.downcast_ref::<TevError>());
+        assert!(matches!(inner, Some(TevError::Desynchronized { written: 5 })), "got {:?}", error);
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        use crate::color::{decode_srgb, encode_srgb, linear_to_srgb, srgb_to_linear};
+
+        assert_eq!(encode_srgb(0.0), 0.0);
+        assert!((encode_srgb(1.0) - 1.0).abs() < 1e-6);
+        assert!((encode_srgb(0.001) - 0.01292).abs() < 1e-6, "linear segment near zero");
+        assert!((encode_srgb(0.214) - 0.5).abs() < 1e-3);
+
+        let original = [0.0, 0.002, 0.05, 0.5, 1.0];
+        let mut values = original;
+        linear_to_srgb(&mut values);
+        srgb_to_linear(&mut values);
+        for (a, b) in values.iter().zip(original) {
+            assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+        }
+
+        let mut client = TevClient::from_writer(Vec::new());
+        client.update_with("test", 0, 0, 1, 1, &["R", "G"], &[0.5, 1.0], decode_srgb).unwrap();
+        assert_eq!(single_update(client.get_ref()).data, [decode_srgb(0.5), decode_srgb(1.0)]);
+    }
+
+    #[test]
+    fn update_pitched_skips_padding() {
+        let mut client = TevClient::from_writer(Vec::new());
+        // two rows of two RG pixels, each row padded to 6 values, without the padding after the last row
+        let data = [1.0, 2.0, 3.0, 4.0, -1.0, -1.0, 5.0, 6.0, 7.0, 8.0];
+        client.update_pitched("test", 0, 0, 2, 2, &["R", "G"], &data, 6).unwrap();
+
+        assert_eq!(single_update(client.get_ref()).data, [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]);
+
+        assert!(client.update_pitched("test", 0, 0, 2, 2, &["R", "G"], &data, 3).is_err());
+        assert!(client.update_pitched("test", 0, 0, 2, 2, &["R", "G"], &data[..9], 6).is_err());
+    }
+
+    #[test]
+    fn error_messages() {
+        use std::error::Error;
+
+        let error = TevError::NoSocketResponse { read: "tev: unknown option --foo\n".to_string() };
+        assert_eq!(error.to_string(), "stdout did not contain socket, got 'tev: unknown option --foo'");
+        assert!(error.source().is_none());
+
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused");
+        let error = TevError::TcpConnect { host: "127.0.0.1:14158".to_string(), io };
+        assert_eq!(error.to_string(), "error during attempted tcp connection to '127.0.0.1:14158': refused");
+        assert_eq!(error.source().unwrap().to_string(), "refused");
+    }
+
+    #[test]
+    fn max_packet_size() {
+        let mut client = TevClient::from_writer(Vec::new());
+        client.set_max_packet_size(Some(16));
+        client.send(PacketCloseImage { image_name: "a" }).unwrap();
+
+        let small = PacketCloseImage { image_name: "b" };
+        let large = PacketCloseImage { image_name: "a_long_name.exr" };
+        let error = client.send_batch(&[&small, &large]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        let inner = error.get_ref().and_then(|e| e.downcast_ref::<TevError>());
+        assert!(matches!(inner, Some(TevError::PacketTooLarge { len: 21, max: 16 })), "got {:?}", error);
+        assert_eq!(client.get_ref().len(), 7);
+
+        for protocol in [TevProtocol::Current, TevProtocol::Legacy] {
+            client.set_protocol_version(protocol);
+            let open = PacketOpenImage { image_name: "a_long_name.exr", grab_focus: false, channel_selector: "" };
+            let error = client.send_open(open).unwrap_err();
+            let inner = error.get_ref().and_then(|e| e.downcast_ref::<TevError>());
+            assert!(matches!(inner, Some(TevError::PacketTooLarge { max: 16, .. })), "got {:?}", error);
+            let error = client.open_images(&[("b", "", false), ("a_long_name.exr", "", false)]).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+            assert_eq!(client.get_ref().len(), 7);
+        }
+
+        client.set_max_packet_size(None);
+        client.send(large).unwrap();
+        // the limit is enforced while serializing, before the whole update is copied into the buffer
+        let data = vec![0.0; 1 << 20];
+        let mut client = TevClient::from_writer(Vec::new());
+        client.set_max_packet_size(Some(1024));
+        let update = PacketUpdateImage::interleaved("a", &["R"], 0, 0, 1024, 1024, &data).unwrap();
+        assert!(client.send_update(update.as_packet()).is_err());
+        assert!(client.buffer.capacity() <= 1024, "buffer grew to {}", client.buffer.capacity());
+        assert!(client.get_ref().is_empty());
+    }
+
+    #[test]
+    fn send_raw_checks_framing() {
+        use crate::protocol::encode_packet;
+
+        let mut bytes = encode_packet(&PacketCloseImage { image_name: "a" }).unwrap();
+        bytes.extend(encode_packet(&PacketCloseImage { image_name: "b" }).unwrap());
+
+        let mut client = TevClient::from_writer(Vec::new());
+        client.send_raw(&bytes).unwrap();
+        client.send_raw(&[]).unwrap();
+        assert_eq!(client.get_ref(), &bytes);
+
+        assert_eq!(client.send_raw(&bytes[..bytes.len() - 1]).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(client.send_raw(&[0, 0, 0, 0]).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(client.get_ref(), &bytes);
+    }
+
+    #[test]
+    fn update_flipped_reverses_rows() {
+        let mut client = TevClient::from_writer(Vec::new());
+        let bottom_up = [5.0, 6.0, 3.0, 4.0, 1.0, 2.0];
+        client.update_flipped("test", 0, 0, 1, 3, &["R", "G"], &bottom_up).unwrap();
+
+        assert_eq!(single_update(client.get_ref()).data, [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+
+        let error = client.update_flipped("test", 0, 0, 1, 3, &["R", "G"], &bottom_up[1..]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn show_diff_modes() {
+        let a = [1.0, 0.5, 0.0, 2.0];
+        let b = [0.5, 0.5, 1.0, 1.0];
+        for (mode, expected) in [(DiffMode::Absolute, [0.5, 0.0, 1.0, 1.0]), (DiffMode::Signed, [0.5, 0.0, -1.0, 1.0])] {
+            let mut client = TevClient::from_writer(Vec::new());
+            client.show_diff("diff", 2, 1, &["R", "G"], &a, &b, mode).unwrap();
+
+            assert_eq!(created_update(client.get_ref()).data, expected);
+        }
+
+        let mut client = TevClient::from_writer(Vec::new());
+        let error = client.show_diff("diff", 2, 1, &["R", "G"], &a, &b[1..], DiffMode::Absolute).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(client.get_ref().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reconnect_or_respawn() {
+        let dir = std::env::temp_dir().join(format!("tev_client_respawn_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let host_file = dir.join("host");
+
+        let mut command = std::process::Command::new("sh");
+        command.arg("-c").arg(format!("echo \"Initialized IPC, listening on $(cat '{}')\"", host_file.display()));
+
+        let first = TcpListener::bind("127.0.0.1:0").unwrap();
+        let first_host = first.local_addr().unwrap().to_string();
+        std::fs::write(&host_file, &first_host).unwrap();
+        let mut client = TevClient::spawn(command).unwrap();
+        client.child_mut().unwrap().wait().unwrap();
+
+        // tev is still running, so the same instance is used again
+        let second = TcpListener::bind("127.0.0.1:0").unwrap();
+        let second_host = second.local_addr().unwrap().to_string();
+        std::fs::write(&host_file, &second_host).unwrap();
+        client.reconnect_or_respawn().unwrap();
+        assert_eq!(client.reported_host(), Some(first_host.as_str()));
+        assert_eq!(client.peer_addr().unwrap().to_string(), first_host);
+
+        // tev is gone, so it's spawned again
+        drop(first);
+        client.reconnect_or_respawn().unwrap();
+        assert_eq!(client.reported_host(), Some(second_host.as_str()));
+        assert_eq!(client.peer_addr().unwrap().to_string(), second_host);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn update_image_data() {
+        let expected = [1.0, 0.5, 0.0, 2.0];
+        let variants = [
+            ImageData::F32(&expected),
+            ImageData::F16(&[0x3c00, 0x3800, 0x0000, 0x4000]),
+        ];
+        for data in variants {
+            let mut client = TevClient::from_writer(Vec::new());
+            client.update("test", 1, 0, 2, 1, &["R", "G"], data).unwrap();
+
+            let update = single_update(client.get_ref());
+            assert_eq!((update.x, update.width), (1, 2));
+            assert_eq!(update.data, expected);
+        }
+
+        let mut client = TevClient::from_writer(Vec::new());
+        for data in [ImageData::U8(&[0; 3]), ImageData::F16(&[0; 5]), ImageData::F32(&[0.0; 3])] {
+            let error = client.update("test", 0, 0, 2, 1, &["R", "G"], data).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        }
+        assert!(client.get_ref().is_empty());
+    }
+
+    #[test]
+    fn spawn_expecting_port() {
+        let mock = MockTev::start().unwrap();
+        let other = TcpListener::bind("127.0.0.1:0").unwrap();
+        let other_line = format!("Initialized IPC, listening on {}", other.local_addr().unwrap());
+
+        #[cfg(windows)]
+        let command = {
+            use std::os::windows::process::CommandExt;
+            let mut command = std::process::Command::new("cmd");
+            command.raw_arg(format!("/C echo {}&& echo {}", other_line, mock.startup_line()));
+            command
+        };
+        #[cfg(not(windows))]
+        let command = {
+            let mut command = std::process::Command::new("sh");
+            command.arg("-c").arg(format!("echo '{}'; echo '{}'", other_line, mock.startup_line()));
+            command
+        };
+
+        let client = TevClient::spawn_expecting_port(command, mock.addr().port()).unwrap();
+        assert_eq!(client.reported_host(), Some(mock.host().as_str()));
+        drop(client);
+        assert_eq!(mock.finish().unwrap(), vec![]);
+
+        let error = TevClient::spawn_expecting_port(MockTev::start().unwrap().stub_command(), 1).unwrap_err();
+        assert!(matches!(error, TevError::NoSocketResponse { .. }), "got {:?}", error);
+    }
+
+    #[test]
+    fn update_from_fn() {
+        let mut client = TevClient::from_writer(Vec::new());
+        client.update_from_fn("test", 1, 2, 2, 2, &["R", "G"], |x, y| [x as f32, y as f32]).unwrap();
+
+        let update = single_update(client.get_ref());
+        assert_eq!((update.x, update.y, update.width, update.height), (1, 2, 2, 2));
+        assert_eq!(update.data, [1.0, 2.0, 2.0, 2.0, 1.0, 3.0, 2.0, 3.0]);
+
+        let error = client.update_from_fn("test", 0, 0, 2, 2, &["R", "G", "B"], |_, _| [0.0; 2]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        let error = client.update_from_fn("test", u32::MAX, 0, 2, 1, &["L"], |_, _| -> [f32; 1] { unreachable!() }).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
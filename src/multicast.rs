@@ -0,0 +1,68 @@
+use std::io;
+use std::io::Write;
+use std::net::TcpStream;
+
+use crate::{encode_framed, TevClient, TevPacket};
+
+/// A group of [TevClient]s that are all sent the same packets, for example to mirror renders to multiple _tev_ instances.
+///
+/// Each packet is only serialized once, the same bytes are then written to every client.
+///
+/// ```no_run
+/// # use tev_client::{TevClient, TevMulticast, PacketCloseImage};
+/// # fn main() -> std::io::Result<()> {
+/// # let local: TevClient = unimplemented!();
+/// # let remote: TevClient = unimplemented!();
+/// let mut multicast = TevMulticast::new(vec![local, remote]);
+/// for (index, error) in multicast.send(&PacketCloseImage { image_name: "test.exr" })? {
+///     eprintln!("failed to send to client {}: {}", index, error);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TevMulticast<W: Write = TcpStream> {
+    clients: Vec<TevClient<W>>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> TevMulticast<W> {
+    /// Create a group that sends to all of `clients`.
+    pub fn new(clients: Vec<TevClient<W>>) -> Self {
+        TevMulticast { clients, buffer: Vec::new() }
+    }
+
+    /// Add another client to the group, it gets index `clients().len()`.
+    pub fn push(&mut self, client: TevClient<W>) {
+        self.clients.push(client);
+    }
+
+    /// The clients in this group, the indices in errors returned by [TevMulticast::send] refer to this slice.
+    pub fn clients(&self) -> &[TevClient<W>] {
+        &self.clients
+    }
+
+    /// Mutable access to the clients in this group, for example to send a packet to only one of them.
+    pub fn clients_mut(&mut self) -> &mut [TevClient<W>] {
+        &mut self.clients
+    }
+
+    /// Split this group into its clients again.
+    pub fn into_clients(self) -> Vec<TevClient<W>> {
+        self.clients
+    }
+
+    /// Send a packet to every client. A failure for one client doesn't stop the packet from being sent to the others,
+    /// instead the index and error of each client that failed is returned.
+    ///
+    /// Returns an error with kind [io::ErrorKind::InvalidInput] without sending anything if the packet can't be encoded.
+    pub fn send(&mut self, packet: &(impl TevPacket + ?Sized)) -> io::Result<Vec<(usize, io::Error)>> {
+        encode_framed(packet, None, &mut self.buffer)?;
+
+        let buffer = &self.buffer;
+        let errors = self.clients.iter_mut().enumerate()
+            .filter_map(|(i, client)| client.write_bytes(buffer, &[]).err().map(|e| (i, e)))
+            .collect();
+        Ok(errors)
+    }
+}
@@ -0,0 +1,319 @@
+use crate::{
+    PacketCloseImage, PacketCreateImage, PacketOpenImage, PacketReloadImage, PacketUpdateImage, PacketVectorGraphics,
+    PacketType, TevPacket, TevWriter, VgCommand,
+};
+
+/// The owned version of [PacketOpenImage].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketOpenImageOwned {
+    pub image_name: String,
+    pub grab_focus: bool,
+    pub channel_selector: String,
+}
+
+/// The owned version of [PacketReloadImage].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketReloadImageOwned {
+    pub image_name: String,
+    pub grab_focus: bool,
+}
+
+/// The owned version of [PacketUpdateImage].
+///
+/// This is `Send + 'static`, so pixel data can be produced on one thread and sent to _tev_ from another.
+///
+/// ```
+/// # use tev_client::{PacketUpdateImage, PacketUpdateImageOwned, TevClient};
+/// let data = vec![0.5; 2 * 2];
+/// let packet = PacketUpdateImageOwned::from(PacketUpdateImage {
+///     image_name: "test",
+///     grab_focus: false,
+///     channel_names: &["L"],
+///     channel_offsets: &[0],
+///     channel_strides: &[1],
+///     x: 0,
+///     y: 0,
+///     width: 2,
+///     height: 2,
+///     data: &data,
+/// });
+///
+/// std::thread::spawn(move || {
+///     let mut client = TevClient::from_writer(Vec::new());
+///     client.send(packet).unwrap();
+/// }).join().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketUpdateImageOwned {
+    pub image_name: String,
+    pub grab_focus: bool,
+    pub channel_names: Vec<String>,
+    pub channel_offsets: Vec<u64>,
+    pub channel_strides: Vec<u64>,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<f32>,
+}
+
+/// The owned version of [PacketCloseImage].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketCloseImageOwned {
+    pub image_name: String,
+}
+
+/// The owned version of [PacketCreateImage].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketCreateImageOwned {
+    pub image_name: String,
+    pub grab_focus: bool,
+    pub width: u32,
+    pub height: u32,
+    pub channel_names: Vec<String>,
+}
+
+/// The owned version of [PacketVectorGraphics].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketVectorGraphicsOwned {
+    pub image_name: String,
+    pub grab_focus: bool,
+    pub append: bool,
+    pub commands: Vec<VgCommand>,
+}
+
+impl PacketOpenImageOwned {
+    /// Borrow this packet as a [PacketOpenImage].
+    pub fn as_packet(&self) -> PacketOpenImage<'_> {
+        PacketOpenImage {
+            image_name: &self.image_name,
+            grab_focus: self.grab_focus,
+            channel_selector: &self.channel_selector,
+        }
+    }
+}
+
+impl PacketReloadImageOwned {
+    /// Borrow this packet as a [PacketReloadImage].
+    pub fn as_packet(&self) -> PacketReloadImage<'_> {
+        PacketReloadImage {
+            image_name: &self.image_name,
+            grab_focus: self.grab_focus,
+        }
+    }
+}
+
+impl PacketUpdateImageOwned {
+    /// Borrow this packet as a [PacketUpdateImage].
+    pub fn as_packet(&self) -> PacketUpdateImage<'_, String> {
+        PacketUpdateImage {
+            image_name: &self.image_name,
+            grab_focus: self.grab_focus,
+            channel_names: &self.channel_names,
+            channel_offsets: &self.channel_offsets,
+            channel_strides: &self.channel_strides,
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+            data: &self.data,
+        }
+    }
+}
+
+impl PacketCloseImageOwned {
+    /// Borrow this packet as a [PacketCloseImage].
+    pub fn as_packet(&self) -> PacketCloseImage<'_> {
+        PacketCloseImage {
+            image_name: &self.image_name,
+        }
+    }
+}
+
+impl PacketCreateImageOwned {
+    /// Borrow this packet as a [PacketCreateImage].
+    pub fn as_packet(&self) -> PacketCreateImage<'_, String> {
+        PacketCreateImage {
+            image_name: &self.image_name,
+            grab_focus: self.grab_focus,
+            width: self.width,
+            height: self.height,
+            channel_names: &self.channel_names,
+        }
+    }
+}
+
+impl PacketVectorGraphicsOwned {
+    /// Borrow this packet as a [PacketVectorGraphics].
+    pub fn as_packet(&self) -> PacketVectorGraphics<'_> {
+        PacketVectorGraphics {
+            image_name: &self.image_name,
+            grab_focus: self.grab_focus,
+            append: self.append,
+            commands: &self.commands,
+        }
+    }
+}
+
+impl From<&PacketOpenImage<'_>> for PacketOpenImageOwned {
+    fn from(packet: &PacketOpenImage<'_>) -> Self {
+        PacketOpenImageOwned {
+            image_name: packet.image_name.to_string(),
+            grab_focus: packet.grab_focus,
+            channel_selector: packet.channel_selector.to_string(),
+        }
+    }
+}
+
+impl From<&PacketReloadImage<'_>> for PacketReloadImageOwned {
+    fn from(packet: &PacketReloadImage<'_>) -> Self {
+        PacketReloadImageOwned {
+            image_name: packet.image_name.to_string(),
+            grab_focus: packet.grab_focus,
+        }
+    }
+}
+
+impl<'a, S: AsRef<str> + 'a> From<&PacketUpdateImage<'a, S>> for PacketUpdateImageOwned {
+    fn from(packet: &PacketUpdateImage<'a, S>) -> Self {
+        PacketUpdateImageOwned {
+            image_name: packet.image_name.to_string(),
+            grab_focus: packet.grab_focus,
+            channel_names: packet.channel_names.iter().map(|c| c.as_ref().to_string()).collect(),
+            channel_offsets: packet.channel_offsets.to_vec(),
+            channel_strides: packet.channel_strides.to_vec(),
+            x: packet.x,
+            y: packet.y,
+            width: packet.width,
+            height: packet.height,
+            data: packet.data.to_vec(),
+        }
+    }
+}
+
+impl From<&PacketCloseImage<'_>> for PacketCloseImageOwned {
+    fn from(packet: &PacketCloseImage<'_>) -> Self {
+        PacketCloseImageOwned {
+            image_name: packet.image_name.to_string(),
+        }
+    }
+}
+
+impl<'a, S: AsRef<str> + 'a> From<&PacketCreateImage<'a, S>> for PacketCreateImageOwned {
+    fn from(packet: &PacketCreateImage<'a, S>) -> Self {
+        PacketCreateImageOwned {
+            image_name: packet.image_name.to_string(),
+            grab_focus: packet.grab_focus,
+            width: packet.width,
+            height: packet.height,
+            channel_names: packet.channel_names.iter().map(|c| c.as_ref().to_string()).collect(),
+        }
+    }
+}
+
+impl From<&PacketVectorGraphics<'_>> for PacketVectorGraphicsOwned {
+    fn from(packet: &PacketVectorGraphics<'_>) -> Self {
+        PacketVectorGraphicsOwned {
+            image_name: packet.image_name.to_string(),
+            grab_focus: packet.grab_focus,
+            append: packet.append,
+            commands: packet.commands.to_vec(),
+        }
+    }
+}
+
+impl From<PacketOpenImage<'_>> for PacketOpenImageOwned {
+    fn from(packet: PacketOpenImage<'_>) -> Self {
+        PacketOpenImageOwned::from(&packet)
+    }
+}
+
+impl From<PacketReloadImage<'_>> for PacketReloadImageOwned {
+    fn from(packet: PacketReloadImage<'_>) -> Self {
+        PacketReloadImageOwned::from(&packet)
+    }
+}
+
+impl<'a, S: AsRef<str> + 'a> From<PacketUpdateImage<'a, S>> for PacketUpdateImageOwned {
+    fn from(packet: PacketUpdateImage<'a, S>) -> Self {
+        PacketUpdateImageOwned::from(&packet)
+    }
+}
+
+impl From<PacketCloseImage<'_>> for PacketCloseImageOwned {
+    fn from(packet: PacketCloseImage<'_>) -> Self {
+        PacketCloseImageOwned::from(&packet)
+    }
+}
+
+impl<'a, S: AsRef<str> + 'a> From<PacketCreateImage<'a, S>> for PacketCreateImageOwned {
+    fn from(packet: PacketCreateImage<'a, S>) -> Self {
+        PacketCreateImageOwned::from(&packet)
+    }
+}
+
+impl From<PacketVectorGraphics<'_>> for PacketVectorGraphicsOwned {
+    fn from(packet: PacketVectorGraphics<'_>) -> Self {
+        PacketVectorGraphicsOwned::from(&packet)
+    }
+}
+
+impl TevPacket for PacketOpenImageOwned {
+    fn packet_type(&self) -> PacketType {
+        self.as_packet().packet_type()
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        self.as_packet().write_to(writer)
+    }
+}
+
+impl TevPacket for PacketReloadImageOwned {
+    fn packet_type(&self) -> PacketType {
+        self.as_packet().packet_type()
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        self.as_packet().write_to(writer)
+    }
+}
+
+impl TevPacket for PacketUpdateImageOwned {
+    fn packet_type(&self) -> PacketType {
+        self.as_packet().packet_type()
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        self.as_packet().write_to(writer)
+    }
+}
+
+impl TevPacket for PacketCloseImageOwned {
+    fn packet_type(&self) -> PacketType {
+        self.as_packet().packet_type()
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        self.as_packet().write_to(writer)
+    }
+}
+
+impl TevPacket for PacketCreateImageOwned {
+    fn packet_type(&self) -> PacketType {
+        self.as_packet().packet_type()
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        self.as_packet().write_to(writer)
+    }
+}
+
+impl TevPacket for PacketVectorGraphicsOwned {
+    fn packet_type(&self) -> PacketType {
+        self.as_packet().packet_type()
+    }
+
+    fn write_to(&self, writer: &mut TevWriter) {
+        self.as_packet().write_to(writer)
+    }
+}
@@ -0,0 +1,110 @@
+//! The wire format of the _tev_ IPC protocol, for tools that need to inspect or produce _tev_ traffic themselves.
+//!
+//! ## Framing
+//!
+//! Every packet starts with its total length in bytes as a `u32`, including these 4 length bytes themselves.
+//! This is followed by the [PacketType] as a single byte and then the fields of the packet, without any padding.
+//!
+//! ## Field types
+//!
+//! * `bool`: a single byte, `0` or `1`
+//! * `u32`, `u64`, `f32`: little-endian, regardless of the byte order of the platform
+//! * `string`: UTF-8 bytes followed by a `'\0'` terminator, so the string itself can't contain `'\0'`
+//!
+//! ## Packets
+//!
+//! The fields of each packet type this crate sends, in order:
+//!
+//! * [PacketType::OpenImageV2]: `grab_focus: bool`, `image_name: string`, `channel_selector: string`
+//! * [PacketType::OpenImage]: `grab_focus: bool`, `image_name: string`,
+//!   only sent with [TevProtocol::Legacy](crate::TevProtocol::Legacy)
+//! * [PacketType::ReloadImage]: `grab_focus: bool`, `image_name: string`
+//! * [PacketType::CloseImage]: `image_name: string`
+//! * [PacketType::CreateImage]: `grab_focus: bool`, `image_name: string`, `width: u32`, `height: u32`,
+//!   `channel_count: u32`, `channel_names: [string; channel_count]`
+//! * [PacketType::UpdateImageV3]: `grab_focus: bool`, `image_name: string`, `channel_count: u32`,
+//!   `channel_names: [string; channel_count]`, `x: u32`, `y: u32`, `width: u32`, `height: u32`,
+//!   `channel_offsets: [u64; channel_count]`, `channel_strides: [u64; channel_count]`,
+//!   followed by `f32` pixel data until the end of the packet
+//! * [PacketType::UpdateImage]: `grab_focus: bool`, `image_name: string`, `channel_name: string`, `x: u32`, `y: u32`,
+//!   `width: u32`, `height: u32`, followed by `width * height` `f32` values,
+//!   only sent with [TevProtocol::Legacy](crate::TevProtocol::Legacy)
+//! * [PacketType::VectorGraphics]: `grab_focus: bool`, `image_name: string`, `append: bool`, `command_count: u32`,
+//!   followed by `command_count` commands that each consist of a command byte and a fixed number of `f32` values,
+//!   see [VgCommand](crate::VgCommand)
+
+use std::io;
+
+use crate::{append_framed, TevPacket};
+
+/// The type of a packet, sent as the first byte after the length.
+///
+/// The older versions of some packets are still accepted by _tev_, but this crate only sends the newest ones
+/// unless [TevProtocol::Legacy](crate::TevProtocol::Legacy) is selected.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum PacketType {
+    /// Superseded by [PacketType::OpenImageV2].
+    OpenImage = 0,
+    ReloadImage = 1,
+    CloseImage = 2,
+    /// Superseded by [PacketType::UpdateImageV3].
+    UpdateImage = 3,
+    CreateImage = 4,
+    /// Superseded by [PacketType::UpdateImageV3].
+    UpdateImageV2 = 5,
+    UpdateImageV3 = 6,
+    OpenImageV2 = 7,
+    VectorGraphics = 8,
+}
+
+impl PacketType {
+    /// The packet type with the given byte value, if there is one.
+    pub fn from_u8(value: u8) -> Option<PacketType> {
+        let packet_type = match value {
+            0 => PacketType::OpenImage,
+            1 => PacketType::ReloadImage,
+            2 => PacketType::CloseImage,
+            3 => PacketType::UpdateImage,
+            4 => PacketType::CreateImage,
+            5 => PacketType::UpdateImageV2,
+            6 => PacketType::UpdateImageV3,
+            7 => PacketType::OpenImageV2,
+            8 => PacketType::VectorGraphics,
+            _ => return None,
+        };
+        Some(packet_type)
+    }
+}
+
+/// Append `packet` to `buffer` including its length prefix, exactly as [TevClient](crate::TevClient) would write it.
+/// This allows sending packets over a custom transport.
+///
+/// Returns an error with kind [io::ErrorKind::InvalidInput] if the packet contains a string that can't be sent
+/// or is too large for the length prefix, in that case `buffer` is left unchanged.
+///
+/// ```
+/// # use tev_client::PacketCloseImage;
+/// # use tev_client::protocol::encode;
+/// let mut buffer = Vec::new();
+/// encode(&PacketCloseImage { image_name: "test.exr" }, &mut buffer).unwrap();
+/// assert_eq!(buffer, b"\x0e\0\0\0\x02test.exr\0");
+/// ```
+pub fn encode(packet: &(impl TevPacket + ?Sized), buffer: &mut Vec<u8>) -> io::Result<()> {
+    append_framed(packet, None, buffer)
+}
+
+/// Serialize `packet` including its length prefix into a new buffer, see [encode].
+/// The result can be stored and sent as often as needed with [TevClient::send_raw](crate::TevClient::send_raw).
+///
+/// ```
+/// # use tev_client::PacketCloseImage;
+/// # use tev_client::protocol::encode_packet;
+/// let bytes = encode_packet(&PacketCloseImage { image_name: "test.exr" }).unwrap();
+/// assert_eq!(bytes, b"\x0e\0\0\0\x02test.exr\0");
+/// ```
+pub fn encode_packet(packet: &(impl TevPacket + ?Sized)) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    encode(packet, &mut buffer)?;
+    Ok(buffer)
+}
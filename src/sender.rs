@@ -0,0 +1,92 @@
+use std::io;
+use std::io::Write;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::{TevClient, TevPacket};
+
+type BoxedPacket = Box<dyn TevPacket + Send>;
+
+/// A cloneable handle that queues packets for a [TevClient] owned by a background thread.
+/// Constructed using [TevClient::into_sender].
+///
+/// [TevSender::send] never blocks on the socket, so this can be shared between many threads
+/// or used from an event loop that must not stall while _tev_ is busy.
+///
+/// ```no_run
+/// # use tev_client::{TevClient, PacketCloseImageOwned};
+/// # fn main() -> std::io::Result<()> {
+/// # let client: TevClient = unimplemented!();
+/// let sender = client.into_sender(|error| eprintln!("failed to send to tev: {}", error));
+///
+/// let worker = sender.clone();
+/// std::thread::spawn(move || {
+///     worker.send(PacketCloseImageOwned { image_name: "test.exr".to_string() }).unwrap();
+/// });
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TevSender {
+    queue: mpsc::Sender<BoxedPacket>,
+}
+
+impl<W: Write + Send + 'static> TevClient<W> {
+    /// Move this client to a new background thread and return a [TevSender] that queues packets for it.
+    ///
+    /// The packets are written in the order they were queued. Errors can't be returned to the sender,
+    /// so each failed packet is reported to `on_error` on the background thread instead,
+    /// after which the remaining packets are still attempted.
+    /// The thread stops and drops the client once all clones of the [TevSender] have been dropped
+    /// and the queued packets are written.
+    pub fn into_sender(mut self, mut on_error: impl FnMut(io::Error) + Send + 'static) -> TevSender {
+        let (queue, receiver) = mpsc::channel::<BoxedPacket>();
+
+        thread::spawn(move || {
+            for packet in receiver {
+                if let Err(e) = self.send_ref(&*packet) {
+                    on_error(e);
+                }
+            }
+        });
+
+        TevSender { queue }
+    }
+}
+
+impl TevSender {
+    /// Queue `packet` to be written by the background thread. Use the owned packet types like
+    /// [PacketUpdateImageOwned](crate::PacketUpdateImageOwned), borrowed packets can't outlive this call.
+    ///
+    /// Returns an error with kind [io::ErrorKind::BrokenPipe] if the background thread has stopped,
+    /// which only happens if it panicked.
+    pub fn send(&self, packet: impl TevPacket + Send + 'static) -> io::Result<()> {
+        self.queue.send(Box::new(packet))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the tev sender thread has stopped"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{PacketCloseImageOwned, TevClient};
+    use crate::test_util::{DecodedPacket, MockTev};
+
+    #[test]
+    fn sender_from_threads() {
+        let mock = MockTev::start().unwrap();
+        let client = TevClient::connect_timeout(&mock.host(), Duration::from_secs(1)).unwrap();
+        let sender = client.into_sender(|e| panic!("failed to send: {}", e));
+
+        let close = PacketCloseImageOwned { image_name: "a".to_string() };
+        sender.send(close.clone()).unwrap();
+        let worker = sender.clone();
+        std::thread::spawn(move || worker.send(close).unwrap()).join().unwrap();
+        drop(sender);
+
+        let packets = mock.finish().unwrap();
+        assert_eq!(packets.len(), 2);
+        assert!(packets.iter().all(|p| matches!(p, DecodedPacket::CloseImage(_))));
+    }
+}
@@ -0,0 +1,92 @@
+use std::io;
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::{TevClient, TevPacket};
+
+/// A [TevClient] that can be cloned and used from multiple threads at the same time.
+///
+/// Every send locks the client for its whole duration, so sends from different threads are serialized:
+/// packets are never interleaved on the connection, but a thread sending a large update blocks the others until
+/// it's done. Use [SharedTevClient::lock] to send multiple packets without other threads sending in between.
+///
+/// ```no_run
+/// # use tev_client::{SharedTevClient, TevClient, PacketCloseImage};
+/// # fn main() -> std::io::Result<()> {
+/// # let client: TevClient = unimplemented!();
+/// let shared = SharedTevClient::new(client);
+/// let handles: Vec<_> = (0..4).map(|i| {
+///     let shared = shared.clone();
+///     std::thread::spawn(move || shared.send(PacketCloseImage { image_name: &format!("{}.exr", i) }))
+/// }).collect();
+/// for handle in handles {
+///     handle.join().unwrap()?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SharedTevClient<W: Write = TcpStream>(Arc<Mutex<TevClient<W>>>);
+
+impl<W: Write> SharedTevClient<W> {
+    /// Wrap `client` so it can be shared.
+    pub fn new(client: TevClient<W>) -> Self {
+        SharedTevClient(Arc::new(Mutex::new(client)))
+    }
+
+    /// Lock the client, blocking until no other thread is using it, and return a guard to use it directly.
+    ///
+    /// A thread panicking while it holds the lock doesn't make the client unusable for the others.
+    /// If a write was interrupted by the panic that's detected by [TevClient::is_desynchronized].
+    pub fn lock(&self) -> MutexGuard<'_, TevClient<W>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the client and send `packet`, see [TevClient::send].
+    pub fn send(&self, packet: impl TevPacket) -> io::Result<()> {
+        self.lock().send(packet)
+    }
+
+    /// Lock the client and send `packet` without consuming it, see [TevClient::send_ref].
+    pub fn send_ref(&self, packet: &(impl TevPacket + ?Sized)) -> io::Result<()> {
+        self.lock().send_ref(packet)
+    }
+}
+
+impl<W: Write> Clone for SharedTevClient<W> {
+    fn clone(&self) -> Self {
+        SharedTevClient(Arc::clone(&self.0))
+    }
+}
+
+impl<W: Write> From<TevClient<W>> for SharedTevClient<W> {
+    fn from(client: TevClient<W>) -> Self {
+        SharedTevClient::new(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{PacketCloseImage, SharedTevClient, TevClient};
+    use crate::test_util::decode_packets;
+
+    #[test]
+    fn shared_client_from_threads() {
+        let shared = SharedTevClient::new(TevClient::from_writer(Vec::new()));
+        let handles: Vec<_> = (0..4).map(|i| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                for _ in 0..10 {
+                    shared.send(PacketCloseImage { image_name: &i.to_string() }).unwrap();
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let packets = decode_packets(shared.lock().get_ref()).unwrap();
+        assert_eq!(packets.len(), 40);
+    }
+}
@@ -0,0 +1,170 @@
+use std::convert::TryFrom;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpStream};
+
+use crate::{check_host, connect_host_timeout, AddressFamily, TevClient, TevError, DEFAULT_CONNECT_TIMEOUT};
+
+const VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const NO_ACCEPTABLE_METHODS: u8 = 0xff;
+const CONNECT: u8 = 1;
+const ADDRESS_IPV4: u8 = 1;
+const ADDRESS_DOMAIN: u8 = 3;
+const ADDRESS_IPV6: u8 = 4;
+
+impl TevClient {
+    /// Connect to the _tev_ instance listening on `host` through the SOCKS5 proxy at `proxy`,
+    /// for example one started with `ssh -D 1080 gpu-box` to view images on a remote machine.
+    ///
+    /// Only proxies that don't require authentication are supported. Host names in `host` are resolved by the proxy,
+    /// so `localhost:14158` refers to the machine the proxy runs on.
+    /// Connecting to the proxy and the handshake both time out after [DEFAULT_CONNECT_TIMEOUT].
+    ///
+    /// ```no_run
+    /// # use tev_client::{TevClient, TevError};
+    /// # fn main() -> Result<(), TevError> {
+    /// let mut client = TevClient::connect_via_socks5("127.0.0.1:1080", "localhost:14158")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// If the proxy refuses the connection the error is [TevError::TcpConnect] with `host`.
+    pub fn connect_via_socks5(proxy: &str, host: &str) -> Result<TevClient, TevError> {
+        check_host(host)?;
+        let mut socket = connect_host_timeout(proxy, AddressFamily::Any, DEFAULT_CONNECT_TIMEOUT)?;
+
+        let handshake = socket.set_read_timeout(Some(DEFAULT_CONNECT_TIMEOUT))
+            .and_then(|()| socks5_connect(&mut socket, host))
+            .and_then(|()| socket.set_read_timeout(None));
+        handshake.map_err(|io| TevError::TcpConnect { host: host.to_string(), io })?;
+
+        Ok(TevClient::wrap(socket))
+    }
+}
+
+/// Perform the SOCKS5 handshake asking the proxy on the other end of `socket` to connect to `host`.
+fn socks5_connect(socket: &mut TcpStream, host: &str) -> io::Result<()> {
+    socket.write_all(&[VERSION, 1, NO_AUTHENTICATION])?;
+    let mut reply = [0; 2];
+    socket.read_exact(&mut reply)?;
+    match reply {
+        [VERSION, NO_AUTHENTICATION] => {}
+        [VERSION, NO_ACCEPTABLE_METHODS] => return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "SOCKS5 proxy requires authentication",
+        )),
+        _ => return Err(invalid_reply()),
+    }
+
+    // check_host already made sure there is a port
+    let (name, port) = host.rsplit_once(':').unwrap();
+    let port: u16 = port.parse().map_err(|_| invalid_host(host))?;
+
+    let mut request = vec![VERSION, CONNECT, 0];
+    match name.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(ADDRESS_IPV4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(ADDRESS_IPV6);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            let len = u8::try_from(name.len()).map_err(|_| invalid_host(host))?;
+            request.push(ADDRESS_DOMAIN);
+            request.push(len);
+            request.extend_from_slice(name.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    socket.write_all(&request)?;
+
+    let mut reply = [0; 4];
+    socket.read_exact(&mut reply)?;
+    if reply[0] != VERSION {
+        return Err(invalid_reply());
+    }
+    if reply[1] != 0 {
+        return Err(reply_error(reply[1]));
+    }
+
+    // skip the address the proxy bound to, it's not needed
+    let address_len = match reply[3] {
+        ADDRESS_IPV4 => 4,
+        ADDRESS_IPV6 => 16,
+        ADDRESS_DOMAIN => {
+            let mut len = [0];
+            socket.read_exact(&mut len)?;
+            len[0] as usize
+        }
+        _ => return Err(invalid_reply()),
+    };
+    let mut bound = vec![0; address_len + 2];
+    socket.read_exact(&mut bound)
+}
+
+fn reply_error(code: u8) -> io::Error {
+    let (kind, message) = match code {
+        2 => (io::ErrorKind::PermissionDenied, "connection not allowed by ruleset"),
+        3 => (io::ErrorKind::Other, "network unreachable"),
+        4 => (io::ErrorKind::Other, "host unreachable"),
+        5 => (io::ErrorKind::ConnectionRefused, "connection refused"),
+        6 => (io::ErrorKind::TimedOut, "TTL expired"),
+        _ => (io::ErrorKind::Other, "general failure"),
+    };
+    io::Error::new(kind, format!("SOCKS5 proxy failed to connect: {} (reply {})", message, code))
+}
+
+fn invalid_reply() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid reply from SOCKS5 proxy")
+}
+
+fn invalid_host(host: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("host '{}' can't be sent to a SOCKS5 proxy", host))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    use crate::{PacketCloseImage, PacketCloseImageOwned, TevClient};
+    use crate::test_util::{DecodedPacket, MockTev};
+
+    #[test]
+    fn connect_via_socks5() {
+        let mock = MockTev::start().unwrap();
+        let proxy = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        let tev_port = mock.addr().port();
+
+        let proxy_thread = std::thread::spawn(move || {
+            let (mut client, _) = proxy.accept().unwrap();
+            let mut greeting = [0; 3];
+            client.read_exact(&mut greeting).unwrap();
+            assert_eq!(greeting, [5, 1, 0]);
+            client.write_all(&[5, 0]).unwrap();
+
+            let mut request = [0; 5 + 9 + 2];
+            client.read_exact(&mut request).unwrap();
+            assert_eq!(&request[..5], &[5, 1, 0, 3, 9]);
+            assert_eq!(&request[5..14], b"localhost");
+            assert_eq!(u16::from_be_bytes([request[14], request[15]]), tev_port);
+            client.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0]).unwrap();
+
+            let mut tev = TcpStream::connect(("127.0.0.1", tev_port)).unwrap();
+            std::io::copy(&mut client, &mut tev).unwrap();
+        });
+
+        let host = format!("localhost:{}", tev_port);
+        let mut client = TevClient::connect_via_socks5(&proxy_addr.to_string(), &host).unwrap();
+        client.send(PacketCloseImage { image_name: "test" }).unwrap();
+        drop(client);
+        proxy_thread.join().unwrap();
+
+        let expected = vec![DecodedPacket::CloseImage(PacketCloseImageOwned { image_name: "test".to_string() })];
+        assert_eq!(mock.finish().unwrap(), expected);
+    }
+}
@@ -0,0 +1,359 @@
+//! Utilities to test code using this crate without a real _tev_ instance.
+//! This module is only available with the `test-util` feature.
+
+use std::convert::TryInto;
+use std::io;
+use std::io::Read;
+use std::net::{SocketAddr, TcpListener};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread::JoinHandle;
+
+use crate::{
+    PacketCloseImageOwned, PacketCreateImageOwned, PacketOpenImageOwned, PacketReloadImageOwned,
+    PacketUpdateImageOwned, PacketVectorGraphicsOwned, PacketType, VgCommand, VgWinding,
+};
+
+/// A fake _tev_ instance that accepts a single connection and decodes all packets sent over it.
+///
+/// ```
+/// # use tev_client::{TevClient, PacketCloseImage};
+/// # use tev_client::test_util::{DecodedPacket, MockTev};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mock = MockTev::start()?;
+///
+/// let mut client = TevClient::connect_timeout(&mock.host(), std::time::Duration::from_secs(1))?;
+/// client.send(PacketCloseImage { image_name: "test.exr" })?;
+/// drop(client);
+///
+/// let packets = mock.finish()?;
+/// assert!(matches!(&packets[..], [DecodedPacket::CloseImage(p)] if p.image_name == "test.exr"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MockTev {
+    addr: SocketAddr,
+    handle: JoinHandle<io::Result<Vec<DecodedPacket>>>,
+}
+
+/// A packet received by [MockTev].
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodedPacket {
+    OpenImage(PacketOpenImageOwned),
+    ReloadImage(PacketReloadImageOwned),
+    CloseImage(PacketCloseImageOwned),
+    CreateImage(PacketCreateImageOwned),
+    UpdateImage(PacketUpdateImageOwned),
+    VectorGraphics(PacketVectorGraphicsOwned),
+    /// A packet with a type this crate doesn't send, `payload` is everything after the type byte.
+    Unknown { kind: u8, payload: Vec<u8> },
+}
+
+impl MockTev {
+    /// Start listening on a free local port. The connection is accepted and read on a background thread.
+    pub fn start() -> io::Result<MockTev> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+
+        let handle = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept()?;
+            let mut bytes = Vec::new();
+            socket.read_to_end(&mut bytes)?;
+            decode_packets(&bytes)
+        });
+
+        Ok(MockTev { addr, handle })
+    }
+
+    /// The address this instance is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The address this instance is listening on, formatted like _tev_ reports it.
+    pub fn host(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// The line _tev_ prints on stdout once it's listening.
+    pub fn startup_line(&self) -> String {
+        format!("Initialized IPC, listening on {}", self.host())
+    }
+
+    /// A command that only prints [MockTev::startup_line], so it can be passed to [TevClient::spawn](crate::TevClient::spawn).
+    pub fn stub_command(&self) -> Command {
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            // a normal argument would be quoted, and cmd would echo the quotes as well
+            let mut command = Command::new("cmd");
+            command.raw_arg(format!("/C echo {}", self.startup_line()));
+            command
+        }
+        #[cfg(not(windows))]
+        {
+            let mut command = Command::new("echo");
+            command.arg(self.startup_line());
+            command
+        }
+    }
+
+    /// Write an executable script called `tev` to `dir` that only prints [MockTev::startup_line], and return its path.
+    ///
+    /// This allows testing code that spawns _tev_ itself end-to-end, by pointing it to the script
+    /// or by putting `dir` in front of `PATH` so [TevClient::spawn_path_default](crate::TevClient::spawn_path_default)
+    /// finds the script instead of the real _tev_.
+    ///
+    /// ```
+    /// # use tev_client::{TevClient, PacketCloseImage};
+    /// # use tev_client::test_util::{DecodedPacket, MockTev};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mock = MockTev::start()?;
+    /// let dir = std::env::temp_dir().join(format!("fake_tev_doc_{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir)?;
+    /// let fake_tev = mock.write_executable(&dir)?;
+    ///
+    /// // the code under test, which would normally spawn the real tev
+    /// let mut client = TevClient::builder().program(&fake_tev).spawn()?;
+    /// client.send(PacketCloseImage { image_name: "test.exr" })?;
+    /// drop(client);
+    ///
+    /// let packets = mock.finish()?;
+    /// assert!(matches!(&packets[..], [DecodedPacket::CloseImage(p)] if p.image_name == "test.exr"));
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(unix)]
+    pub fn write_executable(&self, dir: impl AsRef<Path>) -> io::Result<PathBuf> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.as_ref().join("tev");
+        std::fs::write(&path, format!("#!/bin/sh\necho '{}'\n", self.startup_line()))?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+        Ok(path)
+    }
+
+    /// Wait until the client closes the connection and return all packets that were received.
+    pub fn finish(self) -> io::Result<Vec<DecodedPacket>> {
+        self.handle.join().expect("mock tev thread panicked")
+    }
+}
+
+/// Decode a sequence of framed packets, as written to the socket by [TevClient](crate::TevClient).
+pub fn decode_packets(mut bytes: &[u8]) -> io::Result<Vec<DecodedPacket>> {
+    let mut packets = Vec::new();
+
+    while !bytes.is_empty() {
+        let mut reader = Reader { bytes };
+        let length = reader.u32()? as usize;
+        if length < 4 || length > bytes.len() {
+            return Err(invalid_data(format!("invalid packet length {}", length)));
+        }
+
+        packets.push(decode_packet(&bytes[4..length])?);
+        bytes = &bytes[length..];
+    }
+
+    Ok(packets)
+}
+
+/// Decode a single packet without its length prefix.
+pub fn decode_packet(bytes: &[u8]) -> io::Result<DecodedPacket> {
+    let mut reader = Reader { bytes };
+    let kind = reader.u8()?;
+
+    let packet_type = match PacketType::from_u8(kind) {
+        Some(packet_type) => packet_type,
+        None => return Ok(DecodedPacket::Unknown { kind, payload: reader.bytes.to_vec() }),
+    };
+
+    let packet = match packet_type {
+        PacketType::OpenImageV2 => DecodedPacket::OpenImage(PacketOpenImageOwned {
+            grab_focus: reader.bool()?,
+            image_name: reader.string()?,
+            channel_selector: reader.string()?,
+        }),
+        PacketType::ReloadImage => DecodedPacket::ReloadImage(PacketReloadImageOwned {
+            grab_focus: reader.bool()?,
+            image_name: reader.string()?,
+        }),
+        PacketType::CloseImage => DecodedPacket::CloseImage(PacketCloseImageOwned {
+            image_name: reader.string()?,
+        }),
+        PacketType::CreateImage => {
+            let grab_focus = reader.bool()?;
+            let image_name = reader.string()?;
+            let width = reader.u32()?;
+            let height = reader.u32()?;
+            let channel_count = reader.u32()?;
+            let channel_names = (0..channel_count).map(|_| reader.string()).collect::<io::Result<_>>()?;
+            DecodedPacket::CreateImage(PacketCreateImageOwned { image_name, grab_focus, width, height, channel_names })
+        }
+        PacketType::UpdateImageV3 => {
+            let grab_focus = reader.bool()?;
+            let image_name = reader.string()?;
+            let channel_count = reader.u32()?;
+            let channel_names = (0..channel_count).map(|_| reader.string()).collect::<io::Result<_>>()?;
+            let x = reader.u32()?;
+            let y = reader.u32()?;
+            let width = reader.u32()?;
+            let height = reader.u32()?;
+            let channel_offsets = (0..channel_count).map(|_| reader.u64()).collect::<io::Result<_>>()?;
+            let channel_strides = (0..channel_count).map(|_| reader.u64()).collect::<io::Result<_>>()?;
+
+            // the data is simply the rest of the packet
+            if !reader.bytes.len().is_multiple_of(4) {
+                return Err(invalid_data("update data is not a whole number of floats".to_string()));
+            }
+            let data = (0..reader.bytes.len() / 4).map(|_| reader.f32()).collect::<io::Result<_>>()?;
+
+            DecodedPacket::UpdateImage(PacketUpdateImageOwned {
+                image_name,
+                grab_focus,
+                channel_names,
+                channel_offsets,
+                channel_strides,
+                x,
+                y,
+                width,
+                height,
+                data,
+            })
+        }
+        PacketType::VectorGraphics => {
+            let grab_focus = reader.bool()?;
+            let image_name = reader.string()?;
+            let append = reader.bool()?;
+            let command_count = reader.u32()?;
+            let commands = (0..command_count).map(|_| reader.vg_command()).collect::<io::Result<_>>()?;
+            DecodedPacket::VectorGraphics(PacketVectorGraphicsOwned { image_name, grab_focus, append, commands })
+        }
+        _ => return Ok(DecodedPacket::Unknown { kind, payload: reader.bytes.to_vec() }),
+    };
+
+    if !reader.bytes.is_empty() {
+        return Err(invalid_data(format!("{} trailing bytes after packet of type {}", reader.bytes.len(), kind)));
+    }
+
+    Ok(packet)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The inverse of [TevWriter](crate::TevWriter).
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        if self.bytes.len() < N {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "packet ended early"));
+        }
+        let (head, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(head.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn bool(&mut self) -> io::Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_le_bytes(self.take()?))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let end = self.bytes.iter().position(|&b| b == 0)
+            .ok_or_else(|| invalid_data("string is not null-terminated".to_string()))?;
+        let string = String::from_utf8(self.bytes[..end].to_vec())
+            .map_err(|e| invalid_data(e.to_string()))?;
+        self.bytes = &self.bytes[end + 1..];
+        Ok(string)
+    }
+
+    fn winding(&mut self) -> io::Result<VgWinding> {
+        let value = self.f32()?;
+        if value == VgWinding::CounterClockwise as u8 as f32 {
+            Ok(VgWinding::CounterClockwise)
+        } else if value == VgWinding::Clockwise as u8 as f32 {
+            Ok(VgWinding::Clockwise)
+        } else {
+            Err(invalid_data(format!("invalid winding {}", value)))
+        }
+    }
+
+    fn vg_command(&mut self) -> io::Result<VgCommand> {
+        let kind = self.u8()?;
+        let command = match kind {
+            0 => VgCommand::Save,
+            1 => VgCommand::Restore,
+            2 => VgCommand::FillColor { r: self.f32()?, g: self.f32()?, b: self.f32()?, a: self.f32()? },
+            3 => VgCommand::Fill,
+            4 => VgCommand::StrokeColor { r: self.f32()?, g: self.f32()?, b: self.f32()?, a: self.f32()? },
+            5 => VgCommand::Stroke,
+            6 => VgCommand::BeginPath,
+            7 => VgCommand::ClosePath,
+            8 => VgCommand::PathWinding { winding: self.winding()? },
+            10 => VgCommand::MoveTo { x: self.f32()?, y: self.f32()? },
+            11 => VgCommand::LineTo { x: self.f32()?, y: self.f32()? },
+            12 => VgCommand::ArcTo { x1: self.f32()?, y1: self.f32()?, x2: self.f32()?, y2: self.f32()?, radius: self.f32()? },
+            13 => VgCommand::Arc {
+                cx: self.f32()?,
+                cy: self.f32()?,
+                radius: self.f32()?,
+                angle_begin: self.f32()?,
+                angle_end: self.f32()?,
+                winding: self.winding()?,
+            },
+            14 => VgCommand::BezierTo {
+                c1x: self.f32()?,
+                c1y: self.f32()?,
+                c2x: self.f32()?,
+                c2y: self.f32()?,
+                x: self.f32()?,
+                y: self.f32()?,
+            },
+            15 => VgCommand::Circle { cx: self.f32()?, cy: self.f32()?, radius: self.f32()? },
+            16 => VgCommand::Ellipse { cx: self.f32()?, cy: self.f32()?, rx: self.f32()?, ry: self.f32()? },
+            17 => VgCommand::QuadTo { cx: self.f32()?, cy: self.f32()?, x: self.f32()?, y: self.f32()? },
+            18 => VgCommand::Rect { x: self.f32()?, y: self.f32()?, width: self.f32()?, height: self.f32()? },
+            19 => VgCommand::RoundedRect {
+                x: self.f32()?,
+                y: self.f32()?,
+                width: self.f32()?,
+                height: self.f32()?,
+                radius: self.f32()?,
+            },
+            20 => VgCommand::RoundedRectVarying {
+                x: self.f32()?,
+                y: self.f32()?,
+                width: self.f32()?,
+                height: self.f32()?,
+                radius_top_left: self.f32()?,
+                radius_top_right: self.f32()?,
+                radius_bottom_right: self.f32()?,
+                radius_bottom_left: self.f32()?,
+            },
+            _ => return Err(invalid_data(format!("unknown vector graphics command {}", kind))),
+        };
+        Ok(command)
+    }
+}